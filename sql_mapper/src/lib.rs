@@ -1,4 +1,4 @@
-#![feature(proc_macro_span)]
+#![feature(proc_macro_diagnostic)]
 
 #[macro_use]
 mod r#macro;
@@ -1,17 +1,20 @@
 macro_rules! impl_token_ident {
-    ($name:ident, $string:expr) => {
+    ($name:ident, $string:expr) => { impl_token_ident! { , $name, $string } };
+    ($v:vis, $name:ident, $string:expr) => {
         #[derive(Debug)]
-        struct $name;
+        $v struct $name;
 
         impl Parse for $name {
             fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-                let ident: syn::Ident = input.parse()?;
+                $crate::parse::try_parse(input, |input| {
+                    let ident: syn::Ident = input.parse()?;
 
-                if ident == $string {
-                    Ok(Self)
-                } else {
-                    Err(syn::Error::new(ident.span(), $string))
-                }
+                    if ident == $string {
+                        Ok(Self)
+                    } else {
+                        Err(syn::Error::new(ident.span(), $string))
+                    }
+                })
             }
         }
 
@@ -24,9 +27,10 @@ macro_rules! impl_token_ident {
 }
 
 macro_rules! impl_token_punct {
-    ($name:ident, $punct:tt) => {
+    ($name:ident, $punct:tt) => { impl_token_punct! { , $name, $punct } };
+    ($v:vis, $name:ident, $punct:tt) => {
         #[derive(Debug)]
-        struct $name;
+        $v struct $name;
 
         impl Parse for $name {
             fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
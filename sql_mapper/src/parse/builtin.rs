@@ -0,0 +1,256 @@
+use super::scope::{ColumnType, SqlType};
+
+/// Argument and result types of a built-in function or operator.
+#[derive(Debug)]
+pub struct Signature {
+    pub arguments: Vec<&'static str>,
+    pub result: &'static str,
+}
+
+// https://www.postgresql.org/docs/current/functions-string.html
+// https://www.postgresql.org/docs/current/functions-math.html
+const FUNCTIONS: &[(&str, &[&str], &str)] = &[
+    ("btrim", &["Text"], "Text"),
+    ("btrim", &["Text", "Text"], "Text"),
+    ("char_length", &["Text"], "Int4"),
+    ("initcap", &["Text"], "Text"),
+    ("left", &["Text", "Int4"], "Text"),
+    ("length", &["Text"], "Int4"),
+    ("lower", &["Text"], "Text"),
+    ("lpad", &["Text", "Int4"], "Text"),
+    ("lpad", &["Text", "Int4", "Text"], "Text"),
+    ("ltrim", &["Text"], "Text"),
+    ("ltrim", &["Text", "Text"], "Text"),
+    ("md5", &["Text"], "Text"),
+    ("octet_length", &["Text"], "Int4"),
+    ("repeat", &["Text", "Int4"], "Text"),
+    ("replace", &["Text", "Text", "Text"], "Text"),
+    ("reverse", &["Text"], "Text"),
+    ("right", &["Text", "Int4"], "Text"),
+    ("rpad", &["Text", "Int4"], "Text"),
+    ("rpad", &["Text", "Int4", "Text"], "Text"),
+    ("rtrim", &["Text"], "Text"),
+    ("rtrim", &["Text", "Text"], "Text"),
+    ("split_part", &["Text", "Text", "Int4"], "Text"),
+    ("strpos", &["Text", "Text"], "Int4"),
+    ("substr", &["Text", "Int4"], "Text"),
+    ("substr", &["Text", "Int4", "Int4"], "Text"),
+    ("trim", &["Text"], "Text"),
+    ("upper", &["Text"], "Text"),
+    ("abs", &["Int2"], "Int2"),
+    ("abs", &["Int4"], "Int4"),
+    ("abs", &["Int8"], "Int8"),
+    ("abs", &["Numeric"], "Numeric"),
+    ("abs", &["Float4"], "Float4"),
+    ("abs", &["Float8"], "Float8"),
+    ("ceil", &["Numeric"], "Numeric"),
+    ("ceil", &["Float8"], "Float8"),
+    ("floor", &["Numeric"], "Numeric"),
+    ("floor", &["Float8"], "Float8"),
+    ("mod", &["Int2", "Int2"], "Int2"),
+    ("mod", &["Int4", "Int4"], "Int4"),
+    ("mod", &["Int8", "Int8"], "Int8"),
+    ("mod", &["Numeric", "Numeric"], "Numeric"),
+    ("power", &["Numeric", "Numeric"], "Numeric"),
+    ("power", &["Float8", "Float8"], "Float8"),
+    ("round", &["Numeric"], "Numeric"),
+    ("round", &["Float8"], "Float8"),
+    ("round", &["Numeric", "Int4"], "Numeric"),
+    ("sign", &["Numeric"], "Numeric"),
+    ("sign", &["Float8"], "Float8"),
+    ("sqrt", &["Numeric"], "Numeric"),
+    ("sqrt", &["Float8"], "Float8"),
+    ("trunc", &["Numeric"], "Numeric"),
+    ("trunc", &["Float8"], "Float8"),
+    ("trunc", &["Numeric", "Int4"], "Numeric"),
+    // https://www.postgresql.org/docs/current/functions-textsearch.html
+    // the configuration is a `regconfig`, which is given as a string constant
+    ("to_tsvector", &["Text"], "TsVector"),
+    ("to_tsvector", &["Text", "Text"], "TsVector"),
+    ("to_tsquery", &["Text"], "TsQuery"),
+    ("to_tsquery", &["Text", "Text"], "TsQuery"),
+    ("plainto_tsquery", &["Text"], "TsQuery"),
+    ("plainto_tsquery", &["Text", "Text"], "TsQuery"),
+    ("phraseto_tsquery", &["Text"], "TsQuery"),
+    ("phraseto_tsquery", &["Text", "Text"], "TsQuery"),
+    ("websearch_to_tsquery", &["Text"], "TsQuery"),
+    ("websearch_to_tsquery", &["Text", "Text"], "TsQuery"),
+    ("ts_rank", &["TsVector", "TsQuery"], "Float4"),
+];
+
+// https://www.postgresql.org/docs/current/functions-srf.html
+// https://www.postgresql.org/docs/current/functions-json.html
+// `unnest` is typed by its array argument instead
+const SET_RETURNING_FUNCTIONS: &[(&str, &[&str], &str)] = &[
+    ("generate_series", &["Int4", "Int4"], "Int4"),
+    ("generate_series", &["Int4", "Int4", "Int4"], "Int4"),
+    ("generate_series", &["Int8", "Int8"], "Int8"),
+    ("generate_series", &["Int8", "Int8", "Int8"], "Int8"),
+    ("generate_series", &["Numeric", "Numeric"], "Numeric"),
+    (
+        "generate_series",
+        &["Numeric", "Numeric", "Numeric"],
+        "Numeric",
+    ),
+    ("json_array_elements", &["Json"], "Json"),
+    ("json_array_elements_text", &["Json"], "Text"),
+    ("jsonb_array_elements", &["Jsonb"], "Jsonb"),
+    ("jsonb_array_elements_text", &["Jsonb"], "Text"),
+];
+
+// ordered by implicit casts, each type is implicitly cast to all later ones
+const NUMERIC_TYPES: &[&str] = &["Int2", "Int4", "Int8", "Numeric", "Float4", "Float8"];
+
+// the type Postgres prefers within a category when resolving overloads
+const PREFERRED_TYPES: &[&str] = &["Bool", "Float8", "Text"];
+
+pub fn function_names() -> impl Iterator<Item = String> {
+    let mut names: Vec<_> = FUNCTIONS.iter().map(|(name, _, _)| *name).collect();
+    names.dedup();
+    names.into_iter().map(str::to_owned)
+}
+
+pub fn function_signatures(name: &str) -> Vec<Signature> {
+    FUNCTIONS
+        .iter()
+        .filter(|(n, _, _)| *n == name)
+        .map(|(_, arguments, result)| Signature {
+            arguments: arguments.to_vec(),
+            result,
+        })
+        .collect()
+}
+
+pub fn set_returning_names() -> impl Iterator<Item = String> {
+    let mut names: Vec<_> = SET_RETURNING_FUNCTIONS
+        .iter()
+        .map(|(name, _, _)| *name)
+        .collect();
+    names.dedup();
+    std::iter::once("unnest").chain(names).map(str::to_owned)
+}
+
+pub fn set_returning_signatures(name: &str) -> Vec<Signature> {
+    SET_RETURNING_FUNCTIONS
+        .iter()
+        .filter(|(n, _, _)| *n == name)
+        .map(|(_, arguments, result)| Signature {
+            arguments: arguments.to_vec(),
+            result,
+        })
+        .collect()
+}
+
+/// Functions declared with an output parameter name their column after it, not after the alias.
+pub fn output_column(name: &str) -> Option<&'static str> {
+    match name {
+        "json_array_elements"
+        | "json_array_elements_text"
+        | "jsonb_array_elements"
+        | "jsonb_array_elements_text" => Some("value"),
+        _ => None,
+    }
+}
+
+/// Arithmetic operators take and return the same numeric type, `%` only exists for exact types.
+pub fn arithmetic_signatures(operator: &str) -> Vec<Signature> {
+    NUMERIC_TYPES
+        .iter()
+        .filter(|ty| operator != "%" || !ty.starts_with("Float"))
+        .map(|ty| Signature {
+            arguments: vec![*ty, *ty],
+            result: ty,
+        })
+        .collect()
+}
+
+fn numeric_position(ty: &str) -> Option<usize> {
+    NUMERIC_TYPES.iter().position(|t| *t == ty)
+}
+
+fn can_cast(from: &str, to: &str) -> bool {
+    match (numeric_position(from), numeric_position(to)) {
+        (Some(from), Some(to)) => from <= to,
+        _ => from == to || SqlType::named(from).is_compatible(&SqlType::named(to)),
+    }
+}
+
+/// The type values of both types are converted to, e.g. the results of a `CASE`.
+///
+/// https://www.postgresql.org/docs/current/typeconv-union-case.html
+pub fn common_type<'a>(a: &'a str, b: &'a str) -> Option<&'a str> {
+    if a == b {
+        return Some(a);
+    }
+
+    match (numeric_position(a), numeric_position(b)) {
+        (Some(x), Some(y)) => Some(if x >= y { a } else { b }),
+        _ if SqlType::named(a).is_compatible(&SqlType::named(b)) => Some("Text"),
+        _ => None,
+    }
+}
+
+pub enum Resolution<'a> {
+    Found(&'a Signature),
+    /// only untyped arguments, which fit several signatures
+    Ambiguous,
+    NotFound,
+}
+
+/// Picks the signature for the argument types, preferring exact matches over implicit casts.
+///
+/// https://www.postgresql.org/docs/current/typeconv-func.html
+pub fn resolve<'a>(signatures: &'a [Signature], arguments: &[ColumnType]) -> Resolution<'a> {
+    let candidates: Vec<_> = signatures
+        .iter()
+        .filter(|signature| {
+            signature.arguments.len() == arguments.len()
+                && signature
+                    .arguments
+                    .iter()
+                    .zip(arguments)
+                    .all(|(parameter, argument)| match &argument.ty {
+                        SqlType::Unknown => true,
+                        SqlType::Named(name) => can_cast(name, parameter),
+                    })
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [] => return Resolution::NotFound,
+        [signature] => return Resolution::Found(signature),
+        _ => {}
+    }
+
+    if arguments
+        .iter()
+        .all(|argument| argument.ty == SqlType::Unknown)
+    {
+        return Resolution::Ambiguous;
+    }
+
+    let score = |signature: &Signature| {
+        let exact = signature
+            .arguments
+            .iter()
+            .zip(arguments)
+            .filter(|(parameter, argument)| argument.ty == SqlType::named(parameter))
+            .count();
+        let preferred = signature
+            .arguments
+            .iter()
+            .filter(|parameter| PREFERRED_TYPES.contains(parameter))
+            .count();
+
+        (exact, preferred)
+    };
+
+    // the first of equally good candidates wins
+    let best = candidates
+        .iter()
+        .rev()
+        .max_by_key(|signature| score(signature))
+        .expect("several candidates");
+
+    Resolution::Found(best)
+}
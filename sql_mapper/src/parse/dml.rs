@@ -0,0 +1,857 @@
+use super::{
+    expr::Expression,
+    parse_if_keyword, parse_parenthesized,
+    schema::Schema,
+    scope::{Params, Scope, ScopeColumn, ScopeTable},
+    sql::{
+        indent, As, Comma, EitherStarOrPunctSelectSublist, Flags, From, Into, On, OneOreMany,
+        Projection, ProjectionWalker, Punctuated, WhereClause, WithClause,
+    },
+    try_parse, Identifier, SqlDisplay,
+};
+use syn::{parse::Parse, token::Paren};
+
+/// An `INSERT` of one or more rows of values.
+///
+/// https://www.postgresql.org/docs/current/sql-insert.html
+#[derive(Debug)]
+pub struct InsertStatement {
+    with_clause: Option<WithClause>,
+    insert: Insert,
+    into: Into,
+    table: Identifier,
+    columns: Option<ColumnList>,
+    values_clause: ValuesClause,
+    on_conflict_clause: Option<OnConflictClause>,
+    returning_clause: Option<ReturningClause>,
+}
+
+impl Parse for InsertStatement {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                with_clause: parse_if_keyword(input, "WITH")?,
+                insert: input.parse()?,
+                into: input.parse()?,
+                table: input.parse()?,
+                columns: try_parse(input, ColumnList::parse).ok(),
+                values_clause: input.parse()?,
+                on_conflict_clause: parse_if_keyword(input, "ON")?,
+                returning_clause: parse_if_keyword(input, "RETURNING")?,
+            })
+        })
+    }
+}
+
+impl InsertStatement {
+    pub fn returns_rows(&self) -> bool {
+        self.returning_clause.is_some()
+    }
+
+    /// The columns values are given for, all columns without a column list.
+    fn targets<'a>(&self, table: &'a ScopeTable) -> syn::Result<Vec<&'a ScopeColumn>> {
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => return Ok(table.columns.iter().collect()),
+        };
+
+        let mut targets: Vec<&ScopeColumn> = Vec::new();
+        for ident in &columns.columns.0 {
+            let column = table.column(&ident.id)?;
+            if targets.iter().any(|t| t.name == column.name) {
+                return Err(syn::Error::new(
+                    ident.id.span(),
+                    format!("column `{}` specified more than once", column.name),
+                ));
+            }
+
+            targets.push(column);
+        }
+
+        Ok(targets)
+    }
+}
+
+impl ProjectionWalker for InsertStatement {
+    fn walk_projection(
+        &self,
+        schema: &Schema,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        let statement_scope = statement_scope(schema, &self.with_clause, params)?;
+        let definition = schema.table(&self.table.id)?;
+        let table = ScopeTable::from_definition(self.table.id.to_string(), definition);
+        let targets = self.targets(&table)?;
+
+        // diesel doesn't know about defaults, a single integer primary key is assumed to be a serial
+        let mut keys = definition.keys.iter();
+        let serial = match (keys.next(), keys.next()) {
+            (Some(key), None) => table
+                .columns
+                .iter()
+                .find(|c| *key == c.name)
+                .filter(|c| matches!(c.ty.ty.to_string().as_str(), "Int2" | "Int4" | "Int8"))
+                .map(|c| c.name.as_str()),
+            _ => None,
+        };
+
+        for column in &table.columns {
+            let is_target = targets.iter().any(|t| t.name == column.name);
+            let is_serial = serial == Some(column.name.as_str());
+
+            if !column.ty.nullable && !is_target && !is_serial {
+                return Err(syn::Error::new(
+                    self.table.id.span(),
+                    format!("missing value for NOT NULL column `{}`", column.name),
+                ));
+            }
+        }
+
+        // values cannot reference any columns
+        let scope = statement_scope.nested();
+        for row in &self.values_clause.rows.0 {
+            if row.values.0.len() != targets.len() {
+                return Err(syn::Error::new(
+                    row.paren.span,
+                    format!(
+                        "expected {} values, found {}",
+                        targets.len(),
+                        row.values.0.len()
+                    ),
+                ));
+            }
+
+            for (value, target) in row.values.0.iter().zip(&targets) {
+                infer_assignment(value, target, &scope, params)?;
+            }
+        }
+
+        if let Some(on_conflict_clause) = &self.on_conflict_clause {
+            on_conflict_clause.infer(&statement_scope, &self.table, params)?;
+        }
+
+        if let Some(returning_clause) = &self.returning_clause {
+            let mut scope = statement_scope.nested();
+            scope.push(ScopeTable::from_definition(
+                self.table.id.to_string(),
+                definition,
+            ));
+
+            returning_clause.projection.walk(&scope, params, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Infers a value assigned to a column and checks it fits.
+fn infer_assignment(
+    value: &Expression,
+    target: &ScopeColumn,
+    scope: &Scope,
+    params: &mut Params,
+) -> syn::Result<()> {
+    let ty = value.infer(scope, params, Some(&target.ty))?;
+
+    if !ty.ty.is_compatible(&target.ty.ty) {
+        return Err(syn::Error::new(
+            value.span(),
+            format!(
+                "cannot assign {} to column `{}` of type {}",
+                ty.ty, target.name, target.ty.ty
+            ),
+        ));
+    }
+
+    if ty.nullable && !target.ty.nullable {
+        return Err(syn::Error::new(
+            value.span(),
+            format!("column `{}` is NOT NULL", target.name),
+        ));
+    }
+
+    Ok(())
+}
+
+impl SqlDisplay for InsertStatement {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        fmt_with_clause(&self.with_clause, f, level)?;
+
+        indent(f, level)?;
+        self.insert.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.into.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.table.fmt_sql(f, level)?;
+
+        if let Some(columns) = &self.columns {
+            write!(f, " ")?;
+            columns.fmt_sql(f, level)?;
+        }
+
+        writeln!(f)?;
+        indent(f, level)?;
+        self.values_clause.fmt_sql(f, level)?;
+
+        if let Some(on_conflict_clause) = &self.on_conflict_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            on_conflict_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(returning_clause) = &self.returning_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            returning_clause.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An `UPDATE` of the rows matching the `WHERE` clause.
+///
+/// https://www.postgresql.org/docs/current/sql-update.html
+#[derive(Debug)]
+pub struct UpdateStatement {
+    with_clause: Option<WithClause>,
+    update: Update,
+    table: Identifier,
+    alias: Option<(Option<As>, Identifier)>,
+    set_clause: SetClause,
+    where_clause: Option<WhereClause>,
+    returning_clause: Option<ReturningClause>,
+}
+
+impl Parse for UpdateStatement {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                with_clause: parse_if_keyword(input, "WITH")?,
+                update: input.parse()?,
+                table: input.parse()?,
+                alias: try_parse(input, |input| Ok((input.parse().ok(), input.parse()?))).ok(),
+                set_clause: input.parse()?,
+                where_clause: parse_if_keyword(input, "WHERE")?,
+                returning_clause: parse_if_keyword(input, "RETURNING")?,
+            })
+        })
+    }
+}
+
+impl UpdateStatement {
+    pub fn returns_rows(&self) -> bool {
+        self.returning_clause.is_some()
+    }
+
+    pub fn lint(&self, schema: &Schema, flags: &Flags, params: &mut Params) -> syn::Result<()> {
+        if self.where_clause.is_none() {
+            if flags.contains("require_where") {
+                return Err(syn::Error::new(
+                    self.table.id.span(),
+                    format!("UPDATE of `{}` requires a WHERE clause", self.table.id),
+                ));
+            }
+
+            params.warn(
+                self.table.id.span(),
+                format!(
+                    "UPDATE without WHERE changes every row of `{}`, add `WHERE TRUE` if intended",
+                    self.table.id
+                ),
+            );
+        }
+
+        if flags.contains("deny_key_update") {
+            let definition = schema.table(&self.table.id)?;
+            for assignment in &self.set_clause.assignments.0 {
+                if definition.keys.iter().any(|k| *k == assignment.column.id) {
+                    return Err(syn::Error::new(
+                        assignment.column.id.span(),
+                        format!(
+                            "update of primary key column `{}` is denied",
+                            assignment.column.id
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ProjectionWalker for UpdateStatement {
+    fn walk_projection(
+        &self,
+        schema: &Schema,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        let statement_scope = statement_scope(schema, &self.with_clause, params)?;
+        let scope = table_scope(&statement_scope, &self.table, &self.alias)?;
+        self.set_clause.infer(&scope, params)?;
+
+        if let Some(where_clause) = &self.where_clause {
+            where_clause.condition.infer_condition(&scope, params)?;
+        }
+
+        if let Some(returning_clause) = &self.returning_clause {
+            returning_clause.projection.walk(&scope, params, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for UpdateStatement {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        fmt_with_clause(&self.with_clause, f, level)?;
+
+        indent(f, level)?;
+        self.update.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.table.fmt_sql(f, level)?;
+        fmt_alias(&self.alias, f, level)?;
+
+        writeln!(f)?;
+        indent(f, level)?;
+        self.set_clause.fmt_sql(f, level)?;
+
+        if let Some(where_clause) = &self.where_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            where_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(returning_clause) = &self.returning_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            returning_clause.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A `DELETE` of the rows matching the `WHERE` clause.
+///
+/// https://www.postgresql.org/docs/current/sql-delete.html
+#[derive(Debug)]
+pub struct DeleteStatement {
+    with_clause: Option<WithClause>,
+    delete: Delete,
+    from: From,
+    table: Identifier,
+    alias: Option<(Option<As>, Identifier)>,
+    where_clause: Option<WhereClause>,
+    returning_clause: Option<ReturningClause>,
+}
+
+impl Parse for DeleteStatement {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                with_clause: parse_if_keyword(input, "WITH")?,
+                delete: input.parse()?,
+                from: input.parse()?,
+                table: input.parse()?,
+                alias: try_parse(input, |input| Ok((input.parse().ok(), input.parse()?))).ok(),
+                where_clause: parse_if_keyword(input, "WHERE")?,
+                returning_clause: parse_if_keyword(input, "RETURNING")?,
+            })
+        })
+    }
+}
+
+impl DeleteStatement {
+    pub fn returns_rows(&self) -> bool {
+        self.returning_clause.is_some()
+    }
+
+    pub fn lint(&self, flags: &Flags) -> syn::Result<()> {
+        if self.where_clause.is_none() && flags.contains("require_where") {
+            return Err(syn::Error::new(
+                self.table.id.span(),
+                format!("DELETE from `{}` requires a WHERE clause", self.table.id),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl ProjectionWalker for DeleteStatement {
+    fn walk_projection(
+        &self,
+        schema: &Schema,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        let statement_scope = statement_scope(schema, &self.with_clause, params)?;
+        let scope = table_scope(&statement_scope, &self.table, &self.alias)?;
+
+        if let Some(where_clause) = &self.where_clause {
+            where_clause.condition.infer_condition(&scope, params)?;
+        }
+
+        if let Some(returning_clause) = &self.returning_clause {
+            returning_clause.projection.walk(&scope, params, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for DeleteStatement {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        fmt_with_clause(&self.with_clause, f, level)?;
+
+        indent(f, level)?;
+        self.delete.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.from.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.table.fmt_sql(f, level)?;
+        fmt_alias(&self.alias, f, level)?;
+
+        if let Some(where_clause) = &self.where_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            where_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(returning_clause) = &self.returning_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            returning_clause.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Scope of the CTEs of a statement, the scopes of its clauses are nested in it.
+fn statement_scope<'a>(
+    schema: &'a Schema,
+    with_clause: &Option<WithClause>,
+    params: &mut Params,
+) -> syn::Result<Scope<'a>> {
+    let mut scope = Scope::new(schema);
+    if let Some(with_clause) = with_clause {
+        with_clause.register(&mut scope, params)?;
+    }
+
+    Ok(scope)
+}
+
+/// Scope of the single table an `UPDATE` or `DELETE` works on.
+fn table_scope<'a>(
+    statement_scope: &'a Scope,
+    table: &Identifier,
+    alias: &Option<(Option<As>, Identifier)>,
+) -> syn::Result<Scope<'a>> {
+    let name = match alias {
+        Some((_, alias)) => alias.id.to_string(),
+        None => table.id.to_string(),
+    };
+
+    let mut scope = statement_scope.nested();
+    scope.push(ScopeTable::from_definition(
+        name,
+        statement_scope.schema.table(&table.id)?,
+    ));
+
+    Ok(scope)
+}
+
+fn fmt_with_clause(
+    with_clause: &Option<WithClause>,
+    f: &mut std::fmt::Formatter<'_>,
+    level: usize,
+) -> std::fmt::Result {
+    if let Some(with_clause) = with_clause {
+        indent(f, level)?;
+        with_clause.fmt_sql(f, level)?;
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+fn fmt_alias(
+    alias: &Option<(Option<As>, Identifier)>,
+    f: &mut std::fmt::Formatter<'_>,
+    level: usize,
+) -> std::fmt::Result {
+    if let Some((as_, alias)) = alias {
+        if let Some(as_) = as_ {
+            write!(f, " ")?;
+            as_.fmt_sql(f, level)?;
+        }
+
+        write!(f, " ")?;
+        alias.fmt_sql(f, level)?;
+    }
+
+    Ok(())
+}
+
+/// `SET` with the assigned columns.
+#[derive(Debug)]
+struct SetClause {
+    set: Set,
+    assignments: Punctuated<Assignment, Comma, OneOreMany>,
+}
+
+impl Parse for SetClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            set: input.parse()?,
+            assignments: input.parse()?,
+        })
+    }
+}
+
+impl SetClause {
+    /// Checks the assignments to the columns of the first table in scope.
+    fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<()> {
+        let table = &scope.tables[0];
+
+        let mut assigned: Vec<&ScopeColumn> = Vec::new();
+        for assignment in &self.assignments.0 {
+            let column = table.column(&assignment.column.id)?;
+            if assigned.iter().any(|c| c.name == column.name) {
+                return Err(syn::Error::new(
+                    assignment.column.id.span(),
+                    format!("column `{}` assigned more than once", column.name),
+                ));
+            }
+
+            infer_assignment(&assignment.value, column, scope, params)?;
+            assigned.push(column);
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for SetClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.set.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.assignments.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A `column = value` of the `SET` clause.
+#[derive(Debug)]
+struct Assignment {
+    column: Identifier,
+    eq: Equals,
+    value: Expression,
+}
+
+impl Parse for Assignment {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                column: input.parse()?,
+                eq: input.parse()?,
+                value: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for Assignment {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.column.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.eq.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.value.fmt_sql(f, level)
+    }
+}
+
+/// `ON CONFLICT` with the action for rows that violate the primary key.
+///
+/// https://www.postgresql.org/docs/current/sql-insert.html#SQL-ON-CONFLICT
+#[derive(Debug)]
+struct OnConflictClause {
+    on: On,
+    conflict: Conflict,
+    target: Option<ColumnList>,
+    do_: Do,
+    action: ConflictAction,
+}
+
+impl Parse for OnConflictClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            on: input.parse()?,
+            conflict: input.parse()?,
+            target: try_parse(input, ColumnList::parse).ok(),
+            do_: input.parse()?,
+            action: input.parse()?,
+        })
+    }
+}
+
+impl OnConflictClause {
+    fn infer(
+        &self,
+        statement_scope: &Scope,
+        table: &Identifier,
+        params: &mut Params,
+    ) -> syn::Result<()> {
+        let definition = statement_scope.schema.table(&table.id)?;
+
+        // diesel only knows about the primary key, so it is the only unique constraint
+        if let Some(target) = &self.target {
+            let scope_table = ScopeTable::from_definition(table.id.to_string(), definition);
+            for column in &target.columns.0 {
+                scope_table.column(&column.id)?;
+            }
+
+            let matches_key = target.columns.0.len() == definition.keys.len()
+                && target
+                    .columns
+                    .0
+                    .iter()
+                    .all(|column| definition.keys.iter().any(|k| *k == column.id));
+
+            if !matches_key {
+                let keys: Vec<_> = definition.keys.iter().map(|k| k.to_string()).collect();
+                return Err(syn::Error::new(
+                    target.columns.0[0].id.span(),
+                    format!(
+                        "ON CONFLICT target must be the primary key ({}) of `{}`",
+                        keys.join(", "),
+                        table.id
+                    ),
+                ));
+            }
+        }
+
+        if let ConflictAction::Update(_, set_clause, where_clause) = &self.action {
+            if self.target.is_none() {
+                return Err(syn::Error::new(
+                    table.id.span(),
+                    "ON CONFLICT DO UPDATE requires a conflict target, e.g. `ON CONFLICT (id)`",
+                ));
+            }
+
+            // `excluded` holds the row proposed for insertion
+            let mut scope = statement_scope.nested();
+            scope.push(ScopeTable::from_definition(
+                table.id.to_string(),
+                definition,
+            ));
+            scope.push(ScopeTable::from_definition(
+                "excluded".to_owned(),
+                definition,
+            ));
+
+            set_clause.infer(&scope, params)?;
+
+            if let Some(where_clause) = where_clause {
+                where_clause.condition.infer_condition(&scope, params)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for OnConflictClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.on.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.conflict.fmt_sql(f, level)?;
+
+        if let Some(target) = &self.target {
+            write!(f, " ")?;
+            target.fmt_sql(f, level)?;
+        }
+
+        write!(f, " ")?;
+        self.do_.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.action.fmt_sql(f, level)
+    }
+}
+
+/// `DO NOTHING` or `DO UPDATE SET ...` with an optional `WHERE`.
+#[derive(Debug)]
+enum ConflictAction {
+    Nothing(Nothing),
+    Update(Update, SetClause, Option<WhereClause>),
+}
+
+impl Parse for ConflictAction {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if let Ok(nothing) = input.parse() {
+            return Ok(Self::Nothing(nothing));
+        }
+
+        Ok(Self::Update(
+            input.parse()?,
+            input.parse()?,
+            parse_if_keyword(input, "WHERE")?,
+        ))
+    }
+}
+
+impl SqlDisplay for ConflictAction {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::Nothing(nothing) => nothing.fmt_sql(f, level),
+            Self::Update(update, set_clause, where_clause) => {
+                update.fmt_sql(f, level)?;
+                writeln!(f)?;
+                indent(f, level)?;
+                set_clause.fmt_sql(f, level)?;
+
+                if let Some(where_clause) = where_clause {
+                    writeln!(f)?;
+                    indent(f, level)?;
+                    where_clause.fmt_sql(f, level)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The target columns of an `INSERT`.
+#[derive(Debug)]
+struct ColumnList {
+    columns: Punctuated<Identifier, Comma, OneOreMany>,
+}
+
+impl Parse for ColumnList {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let (_, columns) = parse_parenthesized(input)?;
+        Ok(Self { columns })
+    }
+}
+
+impl SqlDisplay for ColumnList {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "(")?;
+        self.columns.fmt_sql(f, level)?;
+        write!(f, ")")
+    }
+}
+
+/// `VALUES` with one or more rows.
+#[derive(Debug)]
+struct ValuesClause {
+    values: Values,
+    rows: Punctuated<ValuesRow, Comma, OneOreMany>,
+}
+
+impl Parse for ValuesClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                values: input.parse()?,
+                rows: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for ValuesClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.values.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.rows.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A row of values, one for each target column.
+#[derive(Debug)]
+struct ValuesRow {
+    paren: Paren,
+    values: Punctuated<Expression, Comma, OneOreMany>,
+}
+
+impl Parse for ValuesRow {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let (paren, values) = parse_parenthesized(input)?;
+        Ok(Self { paren, values })
+    }
+}
+
+impl SqlDisplay for ValuesRow {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "(")?;
+        self.values.fmt_sql(f, level)?;
+        write!(f, ")")
+    }
+}
+
+/// `RETURNING` with the output columns of a modifying statement.
+#[derive(Debug)]
+pub struct ReturningClause {
+    returning: Returning,
+    pub projection: EitherStarOrPunctSelectSublist,
+}
+
+impl Parse for ReturningClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                returning: input.parse()?,
+                projection: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for ReturningClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.returning.fmt_sql(f, level)?;
+
+        {
+            let level = level + 1;
+            writeln!(f)?;
+            indent(f, level)?;
+            self.projection.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl_token_ident!(Insert, "INSERT");
+impl_token_ident!(Values, "VALUES");
+impl_token_ident!(Returning, "RETURNING");
+impl_token_ident!(Update, "UPDATE");
+impl_token_ident!(Set, "SET");
+impl_token_ident!(Delete, "DELETE");
+impl_token_ident!(Conflict, "CONFLICT");
+impl_token_ident!(Do, "DO");
+impl_token_ident!(Nothing, "NOTHING");
+
+impl_token_punct!(Equals, =);
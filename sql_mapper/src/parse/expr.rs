@@ -0,0 +1,2532 @@
+use super::{
+    builtin::{self, Resolution, Signature},
+    parse_if_keyword, parse_keyword, parse_parenthesized, parse_punct, peek_keyword,
+    scope::{ColumnType, Params, Scope, SqlType},
+    sql::{
+        indent, All, As, By, Comma, Distinct, OneOreMany, Order, Punctuated, Query, SortKey, Star,
+    },
+    try_parse, unknown_error, Identifier, SqlDisplay,
+};
+use proc_macro2::Span;
+use syn::{
+    bracketed,
+    ext::IdentExt,
+    parenthesized,
+    parse::{discouraged::Speculative, Parse, ParseStream},
+    token::{Bracket, Paren},
+    Token,
+};
+
+// https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-PRECEDENCE
+const PRECEDENCE_NOT: u8 = 3;
+const PRECEDENCE_IS: u8 = 4;
+const PRECEDENCE_IN: u8 = 6;
+const PRECEDENCE_UNARY_MINUS: u8 = 10;
+
+/// A value expression, e.g. of a `WHERE` clause or an insert value.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html
+#[derive(Debug)]
+pub enum Expression {
+    Literal(Literal),
+    Parameter(Parameter),
+    Column(ColumnRef),
+    Function(FunctionCall),
+    Cast(TypeCast),
+    Case(CaseExpression),
+    Nested(Nested),
+    Negative(Token![-], Box<Expression>),
+    Not(Not, Box<Expression>),
+    Binary(Box<Expression>, BinaryOperator, Box<Expression>),
+    IsNull(Box<Expression>, IsNull),
+    Subquery(Subquery),
+    Exists(Exists),
+    In(Box<Expression>, InSubquery),
+    Quantified(Box<Expression>, BinaryOperator, ArrayComparison),
+}
+
+impl Parse for Expression {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        parse_expression(input, 0)
+    }
+}
+
+fn parse_expression(input: ParseStream, precedence: u8) -> syn::Result<Expression> {
+    let mut expression = parse_operand(input)?;
+
+    loop {
+        // `::` binds tighter than any operator
+        if input.peek(Token![::]) {
+            expression = Expression::Cast(TypeCast::parse_shorthand(input, expression)?);
+            continue;
+        }
+
+        if PRECEDENCE_IS >= precedence {
+            if let Ok(is_null) = try_parse(input, IsNull::parse) {
+                expression = Expression::IsNull(Box::new(expression), is_null);
+                continue;
+            }
+        }
+
+        if PRECEDENCE_IN >= precedence && (peek_keyword(input, "IN") || peek_keyword(input, "NOT"))
+        {
+            if let Some(in_subquery) = InSubquery::parse_optional(input)? {
+                expression = Expression::In(Box::new(expression), in_subquery);
+                continue;
+            }
+        }
+
+        let fork = input.fork();
+        match fork.parse::<BinaryOperator>() {
+            Ok(op) if op.precedence() >= precedence => {
+                input.advance_to(&fork);
+                if op.is_comparison() && ArrayComparison::peek(input) {
+                    expression = Expression::Quantified(Box::new(expression), op, input.parse()?);
+                    continue;
+                }
+
+                let right = parse_expression(input, op.precedence() + 1)?;
+                expression = Expression::Binary(Box::new(expression), op, Box::new(right));
+            }
+            _ => return Ok(expression),
+        }
+    }
+}
+
+fn parse_operand(input: ParseStream) -> syn::Result<Expression> {
+    if let Ok(not) = try_parse(input, Not::parse) {
+        let inner = parse_expression(input, PRECEDENCE_NOT)?;
+        return Ok(Expression::Not(not, Box::new(inner)));
+    }
+
+    if peek_subquery(input) {
+        return Ok(Expression::Subquery(input.parse()?));
+    }
+
+    if input.peek(Paren) {
+        return Ok(Expression::Nested(input.parse()?));
+    }
+
+    if input.peek(Token![-]) {
+        let minus = input.parse()?;
+        let inner = parse_expression(input, PRECEDENCE_UNARY_MINUS)?;
+        return Ok(Expression::Negative(minus, Box::new(inner)));
+    }
+
+    if input.peek(Token![$]) {
+        return Ok(Expression::Parameter(input.parse()?));
+    }
+
+    if peek_keyword(input, "CASE") {
+        return Ok(Expression::Case(input.parse()?));
+    }
+
+    if peek_keyword(input, "EXISTS") && input.peek2(Paren) {
+        return Ok(Expression::Exists(input.parse()?));
+    }
+
+    if peek_keyword(input, "CAST") && input.peek2(Paren) {
+        return Ok(Expression::Cast(input.parse()?));
+    }
+
+    if input.peek(syn::Ident) && input.peek2(Paren) {
+        return Ok(Expression::Function(input.parse()?));
+    }
+
+    if let Ok(literal) = try_parse(input, Literal::parse) {
+        return Ok(Expression::Literal(literal));
+    }
+
+    Ok(Expression::Column(input.parse()?))
+}
+
+impl Expression {
+    /// Infers the type of the expression, `expected` is the type the context
+    /// asks for and is used to type parameters and untyped literals.
+    pub fn infer(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        expected: Option<&ColumnType>,
+    ) -> syn::Result<ColumnType> {
+        match self {
+            Self::Literal(literal) => Ok(literal.infer(expected)),
+            Self::Parameter(parameter) => {
+                params.bind(&parameter.index, expected)?;
+                Ok(expected
+                    .cloned()
+                    .unwrap_or_else(|| ColumnType::new(SqlType::Unknown, false)))
+            }
+            Self::Column(column) => Ok(column.resolve(scope)?.clone()),
+            Self::Function(function) => function.infer(scope, params, expected),
+            Self::Cast(cast) => cast.infer(scope, params),
+            Self::Case(case) => case.infer(scope, params),
+            Self::Nested(nested) => nested.expression.infer(scope, params, expected),
+            Self::Negative(_, inner) => {
+                let ty = inner.infer(scope, params, expected)?;
+                match &ty.ty {
+                    SqlType::Named(name) if !ty.ty.is_compatible(&SqlType::named("Numeric")) => {
+                        Err(syn::Error::new(
+                            inner.span(),
+                            format!("operator does not exist: -{}", name),
+                        ))
+                    }
+                    _ => Ok(ty),
+                }
+            }
+            Self::Not(_, inner) => inner.infer_condition(scope, params),
+            Self::Binary(left, op, right) => op.infer(left, right, scope, params),
+            Self::IsNull(inner, _) => {
+                inner.infer(scope, params, None)?;
+                Ok(ColumnType::not_null("Bool"))
+            }
+            // no row results in `NULL`
+            Self::Subquery(subquery) => Ok(subquery.column(scope, params)?.with_nullable(true)),
+            Self::Exists(exists) => {
+                exists.subquery.query.walk(scope, params, &mut |_| {})?;
+                Ok(ColumnType::not_null("Bool"))
+            }
+            Self::In(left, in_subquery) => in_subquery.infer(left, scope, params),
+            Self::Quantified(left, _, comparison) => comparison.infer(left, scope, params),
+        }
+    }
+
+    /// Infers a boolean expression, e.g. of a `WHERE` clause.
+    pub fn infer_condition(&self, scope: &Scope, params: &mut Params) -> syn::Result<ColumnType> {
+        let ty = self.infer(scope, params, Some(&ColumnType::not_null("Bool")))?;
+        if !ty.ty.is_compatible(&SqlType::named("Bool")) {
+            return Err(syn::Error::new(
+                self.span(),
+                format!("expected a Bool condition, found {}", ty.ty),
+            ));
+        }
+
+        Ok(ty)
+    }
+
+    /// The directly nested expressions.
+    pub fn children(&self) -> Vec<&Expression> {
+        match self {
+            // subqueries are checked on their own
+            Self::Literal(_)
+            | Self::Parameter(_)
+            | Self::Column(_)
+            | Self::Subquery(_)
+            | Self::Exists(_) => Vec::new(),
+            Self::Function(function) => function.children(),
+            Self::Cast(cast) => vec![&cast.expression],
+            Self::Case(case) => case.children(),
+            Self::Nested(nested) => vec![&nested.expression],
+            Self::Negative(_, inner)
+            | Self::Not(_, inner)
+            | Self::IsNull(inner, _)
+            | Self::In(inner, _) => vec![inner],
+            Self::Binary(left, _, right) => vec![left, right],
+            Self::Quantified(left, _, comparison) => vec![left, &comparison.array],
+        }
+    }
+
+    /// The output column name of the expression without an alias, like Postgres derives it.
+    pub fn derived_name(&self) -> Option<String> {
+        match self {
+            Self::Column(column) => Some(column.column.id.to_string()),
+            Self::Function(function) => Some(function.function_name()),
+            Self::Cast(cast) => cast
+                .expression
+                .derived_name()
+                .or_else(|| Some(cast.ty.sql_type().ok()?.to_string().to_lowercase())),
+            Self::Case(_) => Some("case".to_owned()),
+            Self::Nested(nested) => nested.expression.derived_name(),
+            Self::Subquery(subquery) => subquery.query.derived_name(),
+            Self::Exists(_) => Some("exists".to_owned()),
+            _ => None,
+        }
+    }
+
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            Self::Function(function) if function.is_aggregate() => true,
+            expression => expression
+                .children()
+                .into_iter()
+                .any(Expression::contains_aggregate),
+        }
+    }
+
+    pub fn contains_window(&self) -> bool {
+        match self {
+            Self::Function(FunctionCall { over: Some(_), .. }) => true,
+            expression => expression
+                .children()
+                .into_iter()
+                .any(Expression::contains_window),
+        }
+    }
+
+    /// The names of the windows referenced by `OVER`, which the `WINDOW` clause has to define.
+    pub fn window_references(&self) -> Vec<&Identifier> {
+        let mut references = Vec::new();
+        if let Self::Function(FunctionCall {
+            over: Some(over), ..
+        }) = self
+        {
+            references.extend(over.reference());
+        }
+
+        for child in self.children() {
+            references.extend(child.window_references());
+        }
+
+        references
+    }
+
+    /// The operands of a chain of `AND`s.
+    pub fn conjuncts(&self) -> Vec<&Expression> {
+        match self {
+            Self::Binary(left, BinaryOperator::And, right) => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            }
+            Self::Nested(nested) => nested.expression.conjuncts(),
+            expression => vec![expression],
+        }
+    }
+
+    /// The column of `column = $1` or `column = 'constant'`, which has a single value.
+    pub fn fixed_column(&self) -> Option<&ColumnRef> {
+        fn is_constant(expression: &Expression) -> bool {
+            match expression {
+                Expression::Parameter(_) => true,
+                Expression::Literal(literal) => !matches!(literal, Literal::Null(_)),
+                Expression::Cast(cast) => is_constant(&cast.expression),
+                Expression::Nested(nested) => is_constant(&nested.expression),
+                _ => false,
+            }
+        }
+
+        match self {
+            Self::Binary(left, BinaryOperator::Equal, right) => match (&**left, &**right) {
+                (Self::Column(column), value) | (value, Self::Column(column))
+                    if is_constant(value) =>
+                {
+                    Some(column)
+                }
+                _ => None,
+            },
+            Self::Nested(nested) => nested.expression.fixed_column(),
+            _ => None,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Literal(literal) => literal.span(),
+            Self::Parameter(parameter) => parameter.dollar.span,
+            Self::Column(column) => column.span(),
+            Self::Function(function) => function.name.id.span(),
+            Self::Cast(cast) => cast.span(),
+            Self::Case(case) => case.span,
+            Self::Nested(nested) => nested.paren.span,
+            Self::Negative(minus, _) => minus.span,
+            Self::Not(_, inner) => inner.span(),
+            Self::Binary(left, _, _) => left.span(),
+            Self::IsNull(inner, _) => inner.span(),
+            Self::Subquery(subquery) => subquery.paren.span,
+            Self::Exists(exists) => exists.span,
+            Self::In(left, _) => left.span(),
+            Self::Quantified(left, _, _) => left.span(),
+        }
+    }
+}
+
+impl SqlDisplay for Expression {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::Literal(literal) => literal.fmt_sql(f, level),
+            Self::Parameter(parameter) => parameter.fmt_sql(f, level),
+            Self::Column(column) => column.fmt_sql(f, level),
+            Self::Function(function) => function.fmt_sql(f, level),
+            Self::Cast(cast) => cast.fmt_sql(f, level),
+            Self::Case(case) => case.fmt_sql(f, level),
+            Self::Nested(nested) => nested.fmt_sql(f, level),
+            Self::Negative(_, inner) => {
+                write!(f, "-")?;
+                inner.fmt_sql(f, level)
+            }
+            Self::Not(not, inner) => {
+                not.fmt_sql(f, level)?;
+                write!(f, " ")?;
+                inner.fmt_sql(f, level)
+            }
+            Self::Binary(left, op, right) => {
+                left.fmt_sql(f, level)?;
+                write!(f, " {} ", op.as_str())?;
+                right.fmt_sql(f, level)
+            }
+            Self::IsNull(inner, is_null) => {
+                inner.fmt_sql(f, level)?;
+                write!(f, " ")?;
+                is_null.fmt_sql(f, level)
+            }
+            Self::Subquery(subquery) => subquery.fmt_sql(f, level),
+            Self::Exists(exists) => exists.fmt_sql(f, level),
+            Self::In(left, in_subquery) => {
+                left.fmt_sql(f, level)?;
+                write!(f, " ")?;
+                in_subquery.fmt_sql(f, level)
+            }
+            Self::Quantified(left, op, comparison) => {
+                left.fmt_sql(f, level)?;
+                write!(f, " {} ", op.as_str())?;
+                comparison.fmt_sql(f, level)
+            }
+        }
+    }
+}
+
+/// A constant, its type is known without context.
+///
+/// https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-SYNTAX-CONSTANTS
+#[derive(Debug)]
+pub enum Literal {
+    Integer(syn::LitInt),
+    Decimal(syn::LitFloat),
+    /// `'c'` or `"string"`, as multi-character single quotes are no valid rust tokens
+    String(String, Span),
+    Boolean(bool, Span),
+    Null(Span),
+}
+
+impl Parse for Literal {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            if input.peek(syn::Ident) {
+                let ident: syn::Ident = input.parse()?;
+                return match ident.to_string().as_str() {
+                    "NULL" => Ok(Self::Null(ident.span())),
+                    "TRUE" => Ok(Self::Boolean(true, ident.span())),
+                    "FALSE" => Ok(Self::Boolean(false, ident.span())),
+                    _ => Err(syn::Error::new(ident.span(), "expected literal")),
+                };
+            }
+
+            match input.parse()? {
+                syn::Lit::Int(lit) => Ok(Self::Integer(lit)),
+                syn::Lit::Float(lit) => Ok(Self::Decimal(lit)),
+                syn::Lit::Str(lit) => Ok(Self::String(lit.value(), lit.span())),
+                syn::Lit::Char(lit) => Ok(Self::String(lit.value().to_string(), lit.span())),
+                lit => Err(syn::Error::new(lit.span(), "unsupported literal")),
+            }
+        })
+    }
+}
+
+impl Literal {
+    fn infer(&self, expected: Option<&ColumnType>) -> ColumnType {
+        match self {
+            Self::Integer(lit) => match lit.base10_parse::<i32>() {
+                Ok(_) => ColumnType::not_null("Int4"),
+                Err(_) => ColumnType::not_null("Int8"),
+            },
+            Self::Decimal(_) => ColumnType::not_null("Numeric"),
+            // string constants are untyped until the context gives them a type
+            Self::String(_, _) => match expected {
+                Some(expected) if expected.ty != SqlType::Unknown => expected.with_nullable(false),
+                _ => ColumnType::not_null("Text"),
+            },
+            Self::Boolean(_, _) => ColumnType::not_null("Bool"),
+            Self::Null(_) => match expected {
+                Some(expected) => expected.with_nullable(true),
+                None => ColumnType::new(SqlType::Unknown, true),
+            },
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Self::Integer(lit) => lit.span(),
+            Self::Decimal(lit) => lit.span(),
+            Self::String(_, span) | Self::Boolean(_, span) | Self::Null(span) => *span,
+        }
+    }
+}
+
+impl SqlDisplay for Literal {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, _level: usize) -> std::fmt::Result {
+        match self {
+            Self::Integer(lit) => write!(f, "{}", lit.base10_digits()),
+            Self::Decimal(lit) => write!(f, "{}", lit.base10_digits()),
+            Self::String(value, _) => write!(f, "'{}'", value.replace('\'', "''")),
+            Self::Boolean(true, _) => write!(f, "TRUE"),
+            Self::Boolean(false, _) => write!(f, "FALSE"),
+            Self::Null(_) => write!(f, "NULL"),
+        }
+    }
+}
+
+/// A positional parameter like `$1`, typed by the context it is used in.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SQL-EXPRESSIONS-PARAMETERS-POSITIONAL
+#[derive(Debug)]
+pub struct Parameter {
+    dollar: Token![$],
+    pub index: syn::LitInt,
+}
+
+impl Parse for Parameter {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                dollar: input.parse()?,
+                index: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for Parameter {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, _level: usize) -> std::fmt::Result {
+        write!(f, "${}", self.index.base10_digits())
+    }
+}
+
+/// A possibly qualified column name.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SQL-EXPRESSIONS-COLUMN-REFS
+#[derive(Debug)]
+pub struct ColumnRef {
+    pub table: Option<(Identifier, Token![.])>,
+    pub column: Identifier,
+}
+
+impl Parse for ColumnRef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            let first: Identifier = input.parse()?;
+            if !input.peek(Token![.]) {
+                return Ok(Self {
+                    table: None,
+                    column: first,
+                });
+            }
+
+            Ok(Self {
+                table: Some((first, input.parse()?)),
+                column: input.parse()?,
+            })
+        })
+    }
+}
+
+impl ColumnRef {
+    pub fn resolve<'a>(&self, scope: &'a Scope) -> syn::Result<&'a ColumnType> {
+        let table = self.table.as_ref().map(|(table, _)| &table.id);
+        Ok(&scope.resolve(table, &self.column.id)?.ty)
+    }
+
+    /// The names of the scope table and column this reference resolves to.
+    pub fn qualified(&self, scope: &Scope) -> syn::Result<(String, String)> {
+        let table = self.table.as_ref().map(|(table, _)| &table.id);
+        let (table, column) = scope.resolve_qualified(table, &self.column.id)?;
+        Ok((table.name.clone(), column.name.clone()))
+    }
+
+    pub fn span(&self) -> Span {
+        match &self.table {
+            Some((table, _)) => table.id.span(),
+            None => self.column.id.span(),
+        }
+    }
+}
+
+impl SqlDisplay for ColumnRef {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        if let Some((table, _)) = &self.table {
+            table.fmt_sql(f, level)?;
+            write!(f, ".")?;
+        }
+
+        self.column.fmt_sql(f, level)
+    }
+}
+
+/// A function call like `count(*)`.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SQL-EXPRESSIONS-FUNCTION-CALLS
+#[derive(Debug)]
+pub struct FunctionCall {
+    pub name: Identifier,
+    distinct: Option<Distinct>,
+    arguments: EitherStarOrPunctExpression,
+    pub over: Option<Box<OverClause>>,
+}
+
+// https://www.postgresql.org/docs/current/functions-aggregate.html
+const AGGREGATES: &[&str] = &[
+    "avg",
+    "bool_and",
+    "bool_or",
+    "count",
+    "max",
+    "min",
+    "string_agg",
+    "sum",
+];
+
+// https://www.postgresql.org/docs/current/functions-window.html
+const WINDOW_FUNCTIONS: &[&str] = &[
+    "cume_dist",
+    "dense_rank",
+    "first_value",
+    "lag",
+    "last_value",
+    "lead",
+    "nth_value",
+    "ntile",
+    "percent_rank",
+    "rank",
+    "row_number",
+];
+
+impl Parse for FunctionCall {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+
+        let content;
+        parenthesized!(content in input);
+        let distinct = content.parse().ok();
+        let arguments = content.parse()?;
+
+        if !content.is_empty() {
+            return Err(content.error("unexpected token"));
+        }
+
+        Ok(Self {
+            name,
+            distinct,
+            arguments,
+            over: parse_if_keyword(input, "OVER")?,
+        })
+    }
+}
+
+impl FunctionCall {
+    /// Function names are case insensitive, like unquoted identifiers in Postgres.
+    pub fn function_name(&self) -> String {
+        self.name.id.to_string().to_lowercase()
+    }
+
+    /// Aggregates with `OVER` are window functions, which don't group the rows.
+    pub fn is_aggregate(&self) -> bool {
+        self.over.is_none() && AGGREGATES.contains(&self.function_name().as_str())
+    }
+
+    fn arguments(&self) -> Vec<&Expression> {
+        match &self.arguments {
+            EitherStarOrPunctExpression::Star(_) => Vec::new(),
+            EitherStarOrPunctExpression::PunctExpression(arguments) => arguments.0.iter().collect(),
+        }
+    }
+
+    /// The arguments and the expressions of an inline window.
+    fn children(&self) -> Vec<&Expression> {
+        let mut children = self.arguments();
+        if let Some(OverClause {
+            window: Window::Specification(specification),
+            ..
+        }) = self.over.as_deref()
+        {
+            children.extend(specification.expressions());
+        }
+
+        children
+    }
+
+    fn infer(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        expected: Option<&ColumnType>,
+    ) -> syn::Result<ColumnType> {
+        let name = self.function_name();
+        let is_window_function = WINDOW_FUNCTIONS.contains(&name.as_str());
+
+        match &self.over {
+            Some(over) => {
+                if !is_window_function && !AGGREGATES.contains(&name.as_str()) {
+                    return Err(syn::Error::new(
+                        self.name.id.span(),
+                        format!(
+                            "OVER specified, but `{}` is not a window function nor an aggregate function",
+                            name
+                        ),
+                    ));
+                }
+
+                if let Some(nested) = self.arguments().into_iter().find(|a| a.contains_window()) {
+                    return Err(syn::Error::new(
+                        nested.span(),
+                        "window function calls cannot be nested",
+                    ));
+                }
+
+                over.infer(scope, params)?;
+            }
+            None if is_window_function => {
+                return Err(syn::Error::new(
+                    self.name.id.span(),
+                    format!("window function `{}` requires an OVER clause", name),
+                ));
+            }
+            None => {}
+        }
+
+        if let EitherStarOrPunctExpression::Star(_) = &self.arguments {
+            if name == "count" {
+                return Ok(ColumnType::not_null("Int8"));
+            }
+
+            // unknown functions are reported below
+            if self.is_aggregate() {
+                return Err(syn::Error::new(
+                    self.name.id.span(),
+                    format!("`*` is only allowed in `count(*)`, not in `{}`", name),
+                ));
+            }
+        }
+
+        if self.is_aggregate() {
+            if let Some(nested) = self
+                .arguments()
+                .into_iter()
+                .find(|a| a.contains_aggregate())
+            {
+                return Err(syn::Error::new(
+                    nested.span(),
+                    "aggregate function calls cannot be nested",
+                ));
+            }
+        }
+
+        // aggregates of an empty set are `NULL`, except for `count`
+        match name.as_str() {
+            "count" => {
+                self.expect_arguments::<1>(scope, params)?;
+                Ok(ColumnType::not_null("Int8"))
+            }
+            "sum" => {
+                let [argument] = self.expect_arguments(scope, params)?;
+                let ty = match type_name(&argument) {
+                    "Int2" | "Int4" => "Int8",
+                    "Int8" | "Numeric" => "Numeric",
+                    "Float4" => "Float4",
+                    "Float8" => "Float8",
+                    _ => return Err(self.no_such_function(&[argument])),
+                };
+                Ok(ColumnType::new(SqlType::named(ty), true))
+            }
+            "avg" => {
+                let [argument] = self.expect_arguments(scope, params)?;
+                let ty = match type_name(&argument) {
+                    "Int2" | "Int4" | "Int8" | "Numeric" => "Numeric",
+                    "Float4" | "Float8" => "Float8",
+                    _ => return Err(self.no_such_function(&[argument])),
+                };
+                Ok(ColumnType::new(SqlType::named(ty), true))
+            }
+            "min" | "max" => {
+                let [argument] = self.expect_arguments(scope, params)?;
+                if argument.ty == SqlType::Unknown {
+                    return Err(self.no_such_function(&[argument]));
+                }
+                Ok(argument.with_nullable(true))
+            }
+            "bool_and" | "bool_or" => {
+                let [argument] = self.expect_arguments(scope, params)?;
+                if !argument.ty.is_compatible(&SqlType::named("Bool")) {
+                    return Err(self.no_such_function(&[argument]));
+                }
+                Ok(ColumnType::new(SqlType::named("Bool"), true))
+            }
+            "string_agg" => {
+                let text = ColumnType::not_null("Text");
+                let [value, delimiter] =
+                    self.expect_arguments_as(scope, params, &[&text, &text])?;
+                if !value.ty.is_compatible(&text.ty) || !delimiter.ty.is_compatible(&text.ty) {
+                    return Err(self.no_such_function(&[value, delimiter]));
+                }
+                Ok(text.with_nullable(true))
+            }
+            "coalesce" | "greatest" | "least" => {
+                let (ty, arguments) = infer_common(&self.arguments(), scope, params, &name)?;
+                if arguments.is_empty() {
+                    return Err(self.no_such_function(&arguments));
+                }
+
+                // `NULL`s are skipped, so only all `NULL` arguments are `NULL`
+                let nullable = arguments.iter().all(|argument| argument.nullable);
+                Ok(ColumnType::new(ty, nullable))
+            }
+            "row_number" | "rank" | "dense_rank" => {
+                self.expect_arguments::<0>(scope, params)?;
+                Ok(ColumnType::not_null("Int8"))
+            }
+            "percent_rank" | "cume_dist" => {
+                self.expect_arguments::<0>(scope, params)?;
+                Ok(ColumnType::not_null("Float8"))
+            }
+            "ntile" => {
+                let int = ColumnType::not_null("Int4");
+                let [buckets] = self.expect_arguments_as(scope, params, &[&int])?;
+                if !buckets.ty.is_compatible(&int.ty) {
+                    return Err(self.no_such_function(&[buckets]));
+                }
+                Ok(int.with_nullable(buckets.nullable))
+            }
+            // values of other rows are `NULL` outside of the partition or frame
+            "lag" | "lead" => {
+                let (value, rest) = match self.arguments()[..] {
+                    [value, ref rest @ ..] if rest.len() <= 2 => (value, rest.to_vec()),
+                    _ => return Err(self.arity_error(3)),
+                };
+
+                let mut ty = value.infer(scope, params, None)?;
+                if let Some(offset) = rest.first() {
+                    self.expect_integer(offset, scope, params)?;
+                }
+
+                if let Some(default) = rest.get(1) {
+                    let (common, _) =
+                        infer_common(&[value, default], scope, params, &self.function_name())?;
+                    ty.ty = common;
+                }
+
+                Ok(ty.with_nullable(true))
+            }
+            "first_value" | "last_value" => {
+                let [value] = self.expect_arguments(scope, params)?;
+                Ok(value.with_nullable(true))
+            }
+            "nth_value" => {
+                let (value, n) = match self.arguments()[..] {
+                    [value, n] => (value, n),
+                    _ => return Err(self.arity_error(2)),
+                };
+
+                let ty = value.infer(scope, params, None)?;
+                self.expect_integer(n, scope, params)?;
+                Ok(ty.with_nullable(true))
+            }
+            "nullif" => {
+                let (value, other) = match self.arguments()[..] {
+                    [value, other] => (value, other),
+                    _ => return Err(self.arity_error(2)),
+                };
+
+                // `NULL` if both are equal
+                let (ty, _) = infer_operands(value, other, scope, params)?;
+                Ok(ty.with_nullable(true))
+            }
+            // one row per element, `expected` types an untyped array
+            "unnest" => {
+                let array = self.infer_unnest_array(scope, params, expected)?;
+                match array.ty.element() {
+                    Some(element) => Ok(ColumnType::new(element, false)),
+                    None if array.ty == SqlType::Unknown => Ok(array),
+                    None => Err(self.no_such_function(&[array])),
+                }
+            }
+            name => self.infer_builtin(name, scope, params),
+        }
+    }
+
+    fn infer_builtin(
+        &self,
+        name: &str,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<ColumnType> {
+        let signatures = builtin::function_signatures(name);
+        if signatures.is_empty() {
+            return Err(unknown_error(
+                &self.name.id,
+                format!("unknown function `{}`", self.name.id),
+                AGGREGATES
+                    .iter()
+                    .map(|name| name.to_string())
+                    .chain(builtin::function_names()),
+            ));
+        }
+
+        self.infer_signature(&signatures, scope, params)
+    }
+
+    /// The column type of a set-returning function in `FROM`. Its rows are never `NULL`, `NULL`
+    /// arguments return no rows.
+    pub fn infer_set_returning(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<ColumnType> {
+        let name = self.function_name();
+        if name == "unnest" {
+            let array = self.infer_unnest_array(scope, params, None)?;
+            return match array.ty.element() {
+                Some(element) => Ok(ColumnType::new(element, false)),
+                None if array.ty == SqlType::Unknown => Err(syn::Error::new(
+                    self.name.id.span(),
+                    "cannot infer the array type, add a cast like `$1::int[]`",
+                )),
+                None => Err(self.no_such_function(&[array])),
+            };
+        }
+
+        let signatures = builtin::set_returning_signatures(&name);
+        if signatures.is_empty() {
+            return Err(unknown_error(
+                &self.name.id,
+                format!("unknown set-returning function `{}`", self.name.id),
+                builtin::set_returning_names(),
+            ));
+        }
+
+        Ok(self
+            .infer_signature(&signatures, scope, params)?
+            .with_nullable(false))
+    }
+
+    /// The type of the array argument of `unnest`, an untyped one is an array of `element`.
+    fn infer_unnest_array(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        element: Option<&ColumnType>,
+    ) -> syn::Result<ColumnType> {
+        let array = match self.arguments()[..] {
+            [array] => array,
+            _ => return Err(self.arity_error(1)),
+        };
+
+        let ty = array.infer(scope, params, None)?;
+        match element.and_then(known) {
+            Some(element) if ty.ty == SqlType::Unknown => array.infer(
+                scope,
+                params,
+                Some(&ColumnType::new(element.ty.array(), false)),
+            ),
+            _ => Ok(ty),
+        }
+    }
+
+    fn infer_signature(
+        &self,
+        signatures: &[Signature],
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<ColumnType> {
+        let arguments = self.arguments();
+        let types = arguments
+            .iter()
+            .map(|argument| argument.infer(scope, params, None))
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        match builtin::resolve(signatures, &types) {
+            Resolution::Found(signature) => {
+                // infer again, so parameters and constants get the argument types
+                let mut nullable = false;
+                for (argument, ty) in arguments.iter().zip(&signature.arguments) {
+                    let expected = ColumnType::not_null(ty);
+                    nullable |= argument.infer(scope, params, Some(&expected))?.nullable;
+                }
+
+                Ok(ColumnType::new(SqlType::named(signature.result), nullable))
+            }
+            Resolution::Ambiguous => Ok(ColumnType::new(SqlType::Unknown, false)),
+            Resolution::NotFound => Err(self.no_such_function(&types)),
+        }
+    }
+
+    /// Infers exactly `N` arguments without an expected type.
+    fn expect_arguments<const N: usize>(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<[ColumnType; N]> {
+        let unknown = ColumnType::new(SqlType::Unknown, false);
+        self.expect_arguments_as(scope, params, &[&unknown; N])
+    }
+
+    /// Infers exactly `N` arguments, untyped arguments get the given types.
+    fn expect_arguments_as<const N: usize>(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        expected: &[&ColumnType; N],
+    ) -> syn::Result<[ColumnType; N]> {
+        let arguments = self.arguments();
+        if arguments.len() != N {
+            return Err(self.arity_error(N));
+        }
+
+        let mut types = Vec::with_capacity(N);
+        for (argument, expected) in arguments.into_iter().zip(expected.iter()) {
+            types.push(argument.infer(scope, params, known(expected).as_ref())?);
+        }
+
+        Ok(types.try_into().expect("argument count was checked"))
+    }
+
+    fn expect_integer(
+        &self,
+        argument: &Expression,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<()> {
+        let int = ColumnType::not_null("Int4");
+        let ty = argument.infer(scope, params, Some(&int))?;
+        if !matches!(type_name(&ty), "Int2" | "Int4") {
+            return Err(syn::Error::new(
+                argument.span(),
+                format!(
+                    "function `{}` expects an integer, found {}",
+                    self.name.id, ty.ty
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn arity_error(&self, expected: usize) -> syn::Error {
+        syn::Error::new(
+            self.name.id.span(),
+            format!(
+                "function `{}` takes {} argument(s), found {}",
+                self.name.id,
+                expected,
+                self.arguments().len()
+            ),
+        )
+    }
+
+    fn no_such_function(&self, arguments: &[ColumnType]) -> syn::Error {
+        let types: Vec<_> = arguments.iter().map(|a| a.ty.to_string()).collect();
+        syn::Error::new(
+            self.name.id.span(),
+            format!(
+                "function `{}({})` does not exist",
+                self.function_name(),
+                types.join(", ")
+            ),
+        )
+    }
+}
+
+fn type_name(ty: &ColumnType) -> &str {
+    match &ty.ty {
+        SqlType::Unknown => "unknown",
+        SqlType::Named(name) => name,
+    }
+}
+
+impl SqlDisplay for FunctionCall {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.name.fmt_sql(f, level)?;
+        write!(f, "(")?;
+
+        if let Some(distinct) = &self.distinct {
+            distinct.fmt_sql(f, level)?;
+            write!(f, " ")?;
+        }
+
+        self.arguments.fmt_sql(f, level)?;
+        write!(f, ")")?;
+
+        if let Some(over) = &self.over {
+            write!(f, " ")?;
+            over.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `OVER` and the window a window function is computed over.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SYNTAX-WINDOW-FUNCTIONS
+#[derive(Debug)]
+pub struct OverClause {
+    over: Over,
+    window: Window,
+}
+
+#[derive(Debug)]
+enum Window {
+    /// a window of the `WINDOW` clause
+    Name(Identifier),
+    Specification(WindowSpecification),
+}
+
+impl Parse for OverClause {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let over = input.parse()?;
+        let window = if input.peek(Paren) {
+            let (_, specification) = parse_parenthesized(input)?;
+            Window::Specification(specification)
+        } else {
+            Window::Name(input.parse()?)
+        };
+
+        Ok(Self { over, window })
+    }
+}
+
+impl OverClause {
+    /// The window name of the `WINDOW` clause this window is based on.
+    pub fn reference(&self) -> Option<&Identifier> {
+        match &self.window {
+            Window::Name(name) => Some(name),
+            Window::Specification(specification) => specification.existing.as_ref(),
+        }
+    }
+
+    fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<()> {
+        match &self.window {
+            Window::Name(_) => Ok(()),
+            Window::Specification(specification) => specification.infer(scope, params),
+        }
+    }
+}
+
+impl SqlDisplay for OverClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.over.fmt_sql(f, level)?;
+        write!(f, " ")?;
+
+        match &self.window {
+            Window::Name(name) => name.fmt_sql(f, level),
+            Window::Specification(specification) => {
+                write!(f, "(")?;
+                specification.fmt_sql(f, level)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// The partitioning, ordering and frame of a window.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SYNTAX-WINDOW-FUNCTIONS
+#[derive(Debug)]
+pub struct WindowSpecification {
+    pub existing: Option<Identifier>,
+    partition_by: Option<(Partition, By, Punctuated<Expression, Comma, OneOreMany>)>,
+    order_by: Option<(Order, By, Punctuated<SortKey, Comma, OneOreMany>)>,
+    frame: Option<WindowFrame>,
+}
+
+impl Parse for WindowSpecification {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let existing = if !peek_keyword(input, "PARTITION")
+            && !peek_keyword(input, "ORDER")
+            && !WindowFrame::peek(input)
+        {
+            input.parse().ok()
+        } else {
+            None
+        };
+
+        Ok(Self {
+            existing,
+            partition_by: if peek_keyword(input, "PARTITION") {
+                Some((input.parse()?, input.parse()?, input.parse()?))
+            } else {
+                None
+            },
+            order_by: if peek_keyword(input, "ORDER") {
+                Some((input.parse()?, input.parse()?, input.parse()?))
+            } else {
+                None
+            },
+            frame: if WindowFrame::peek(input) {
+                Some(input.parse()?)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl WindowSpecification {
+    fn expressions(&self) -> Vec<&Expression> {
+        let mut expressions = Vec::new();
+
+        if let Some((_, _, partition)) = &self.partition_by {
+            expressions.extend(partition.0.iter());
+        }
+
+        if let Some((_, _, sort_keys)) = &self.order_by {
+            expressions.extend(sort_keys.0.iter().map(|sort_key| &sort_key.expression));
+        }
+
+        expressions
+    }
+
+    /// Infers the expressions, where integers are constants and no output column positions.
+    pub fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<()> {
+        for expression in self.expressions() {
+            if expression.contains_window() {
+                return Err(syn::Error::new(
+                    expression.span(),
+                    "window functions are not allowed in window definitions",
+                ));
+            }
+
+            expression.infer(scope, params, None)?;
+        }
+
+        if let Some(frame) = &self.frame {
+            frame.infer(scope, params)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for WindowSpecification {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        let mut separator = "";
+
+        if let Some(existing) = &self.existing {
+            existing.fmt_sql(f, level)?;
+            separator = " ";
+        }
+
+        if let Some((partition, by, expressions)) = &self.partition_by {
+            write!(f, "{}", separator)?;
+            partition.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            by.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            expressions.fmt_sql(f, level)?;
+            separator = " ";
+        }
+
+        if let Some((order, by, sort_keys)) = &self.order_by {
+            write!(f, "{}", separator)?;
+            order.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            by.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            sort_keys.fmt_sql(f, level)?;
+            separator = " ";
+        }
+
+        if let Some(frame) = &self.frame {
+            write!(f, "{}", separator)?;
+            frame.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The rows of the partition a window function sees, relative to the current row.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SYNTAX-WINDOW-FUNCTIONS
+#[derive(Debug)]
+struct WindowFrame {
+    span: Span,
+    /// `ROWS`, `RANGE` or `GROUPS`
+    mode: String,
+    start: FrameBound,
+    end: Option<FrameBound>,
+}
+
+const FRAME_MODES: &[&str] = &["ROWS", "RANGE", "GROUPS"];
+
+#[derive(Debug)]
+enum FrameBound {
+    UnboundedPreceding,
+    Preceding(Box<Expression>),
+    CurrentRow,
+    Following(Box<Expression>),
+    UnboundedFollowing,
+}
+
+impl Parse for WindowFrame {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (span, mode) = FRAME_MODES
+            .iter()
+            .find_map(|mode| Some((parse_keyword(input, mode).ok()?, mode.to_string())))
+            .ok_or_else(|| input.error("expected `ROWS`, `RANGE` or `GROUPS`"))?;
+
+        if parse_keyword(input, "BETWEEN").is_ok() {
+            let start = input.parse()?;
+            parse_keyword(input, "AND")?;
+            Ok(Self {
+                span,
+                mode,
+                start,
+                end: Some(input.parse()?),
+            })
+        } else {
+            Ok(Self {
+                span,
+                mode,
+                start: input.parse()?,
+                end: None,
+            })
+        }
+    }
+}
+
+impl WindowFrame {
+    fn peek(input: ParseStream) -> bool {
+        FRAME_MODES.iter().any(|mode| peek_keyword(input, mode))
+    }
+
+    fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<()> {
+        if let FrameBound::UnboundedFollowing = self.start {
+            return Err(syn::Error::new(
+                self.span,
+                "frame start cannot be UNBOUNDED FOLLOWING",
+            ));
+        }
+
+        if let Some(FrameBound::UnboundedPreceding) = self.end {
+            return Err(syn::Error::new(
+                self.span,
+                "frame end cannot be UNBOUNDED PRECEDING",
+            ));
+        }
+
+        for bound in std::iter::once(&self.start).chain(&self.end) {
+            if let FrameBound::Preceding(offset) | FrameBound::Following(offset) = bound {
+                // `RANGE` offsets depend on the type of the sort key
+                if self.mode == "RANGE" {
+                    offset.infer(scope, params, None)?;
+                    continue;
+                }
+
+                let ty = offset.infer(scope, params, Some(&ColumnType::not_null("Int8")))?;
+                if !matches!(type_name(&ty), "Int2" | "Int4" | "Int8") || ty.nullable {
+                    return Err(syn::Error::new(
+                        offset.span(),
+                        format!("frame offset must be a number of rows, found {}", ty.ty),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for WindowFrame {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "{} ", self.mode)?;
+
+        match &self.end {
+            Some(end) => {
+                write!(f, "BETWEEN ")?;
+                self.start.fmt_sql(f, level)?;
+                write!(f, " AND ")?;
+                end.fmt_sql(f, level)
+            }
+            None => self.start.fmt_sql(f, level),
+        }
+    }
+}
+
+impl Parse for FrameBound {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if parse_keyword(input, "UNBOUNDED").is_ok() {
+            if parse_keyword(input, "PRECEDING").is_ok() {
+                return Ok(Self::UnboundedPreceding);
+            }
+
+            parse_keyword(input, "FOLLOWING")?;
+            return Ok(Self::UnboundedFollowing);
+        }
+
+        if parse_keyword(input, "CURRENT").is_ok() {
+            parse_keyword(input, "ROW")?;
+            return Ok(Self::CurrentRow);
+        }
+
+        let offset = Box::new(input.parse()?);
+        if parse_keyword(input, "PRECEDING").is_ok() {
+            return Ok(Self::Preceding(offset));
+        }
+
+        parse_keyword(input, "FOLLOWING")?;
+        Ok(Self::Following(offset))
+    }
+}
+
+impl SqlDisplay for FrameBound {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::UnboundedPreceding => write!(f, "UNBOUNDED PRECEDING"),
+            Self::Preceding(offset) => {
+                offset.fmt_sql(f, level)?;
+                write!(f, " PRECEDING")
+            }
+            Self::CurrentRow => write!(f, "CURRENT ROW"),
+            Self::Following(offset) => {
+                offset.fmt_sql(f, level)?;
+                write!(f, " FOLLOWING")
+            }
+            Self::UnboundedFollowing => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+/// Infers values which are converted to a common type, e.g. the results of a `CASE`.
+/// Untyped values get the common type, which is `Text` if all are untyped.
+fn infer_common(
+    expressions: &[&Expression],
+    scope: &Scope,
+    params: &mut Params,
+    context: &str,
+) -> syn::Result<(SqlType, Vec<ColumnType>)> {
+    let mut common: Option<String> = None;
+
+    for expression in expressions {
+        if let SqlType::Named(name) = expression.infer(scope, params, None)?.ty {
+            let ty = match &common {
+                None => name,
+                Some(common) => builtin::common_type(common, &name)
+                    .ok_or_else(|| {
+                        syn::Error::new(
+                            expression.span(),
+                            format!(
+                                "{} types {} and {} cannot be matched",
+                                context, common, name
+                            ),
+                        )
+                    })?
+                    .to_owned(),
+            };
+
+            common = Some(ty);
+        }
+    }
+
+    let common = ColumnType::not_null(common.as_deref().unwrap_or("Text"));
+    let types = expressions
+        .iter()
+        .map(|expression| expression.infer(scope, params, Some(&common)))
+        .collect::<syn::Result<_>>()?;
+
+    Ok((common.ty, types))
+}
+
+type PunctExpression = Punctuated<Expression, Comma>;
+impl_either!(Star, PunctExpression);
+
+/// `CASE` with `WHEN` branches and an optional `ELSE`.
+///
+/// https://www.postgresql.org/docs/current/functions-conditional.html#FUNCTIONS-CASE
+#[derive(Debug)]
+pub struct CaseExpression {
+    span: Span,
+    /// `CASE x WHEN value ...`, otherwise `CASE WHEN condition ...`
+    operand: Option<Box<Expression>>,
+    branches: Vec<(When, Expression, Then, Expression)>,
+    else_: Option<(Else, Box<Expression>)>,
+    end: End,
+}
+
+impl Parse for CaseExpression {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let span = parse_keyword(input, "CASE")?;
+        let operand = if peek_keyword(input, "WHEN") {
+            None
+        } else {
+            Some(Box::new(input.parse()?))
+        };
+
+        let mut branches = Vec::new();
+        while peek_keyword(input, "WHEN") {
+            branches.push((
+                input.parse()?,
+                input.parse()?,
+                input.parse()?,
+                input.parse()?,
+            ));
+        }
+
+        if branches.is_empty() {
+            return Err(input.error("expected `WHEN`"));
+        }
+
+        let else_ = if peek_keyword(input, "ELSE") {
+            Some((input.parse()?, Box::new(input.parse()?)))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            span,
+            operand,
+            branches,
+            else_,
+            end: input.parse()?,
+        })
+    }
+}
+
+impl CaseExpression {
+    fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<ColumnType> {
+        for (_, condition, _, _) in &self.branches {
+            match &self.operand {
+                Some(operand) => {
+                    infer_operands(operand, condition, scope, params)?;
+                }
+                None => {
+                    condition.infer_condition(scope, params)?;
+                }
+            }
+        }
+
+        let mut results: Vec<_> = self.branches.iter().map(|(_, _, _, r)| r).collect();
+        if let Some((_, else_)) = &self.else_ {
+            results.push(else_);
+        }
+
+        let (ty, results) = infer_common(&results, scope, params, "CASE")?;
+
+        // without `ELSE` unmatched rows are `NULL`
+        let nullable = self.else_.is_none() || results.iter().any(|result| result.nullable);
+        Ok(ColumnType::new(ty, nullable))
+    }
+
+    fn children(&self) -> Vec<&Expression> {
+        let mut children: Vec<&Expression> = self.operand.iter().map(|o| o.as_ref()).collect();
+
+        for (_, condition, _, result) in &self.branches {
+            children.push(condition);
+            children.push(result);
+        }
+
+        if let Some((_, else_)) = &self.else_ {
+            children.push(else_);
+        }
+
+        children
+    }
+}
+
+impl SqlDisplay for CaseExpression {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "CASE")?;
+
+        if let Some(operand) = &self.operand {
+            write!(f, " ")?;
+            operand.fmt_sql(f, level)?;
+        }
+
+        for (when, condition, then, result) in &self.branches {
+            write!(f, " ")?;
+            when.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            condition.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            then.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            result.fmt_sql(f, level)?;
+        }
+
+        if let Some((else_, result)) = &self.else_ {
+            write!(f, " ")?;
+            else_.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            result.fmt_sql(f, level)?;
+        }
+
+        write!(f, " ")?;
+        self.end.fmt_sql(f, level)
+    }
+}
+
+/// A conversion to a named type.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SQL-SYNTAX-TYPE-CASTS
+#[derive(Debug)]
+pub struct TypeCast {
+    /// `CAST(x AS t)`, otherwise `x::t`
+    cast: Option<(Cast, As)>,
+    expression: Box<Expression>,
+    ty: TypeName,
+}
+
+impl Parse for TypeCast {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let cast = input.parse()?;
+
+        let content;
+        parenthesized!(content in input);
+        let expression = content.parse()?;
+        let as_ = content.parse()?;
+        let ty = content.parse()?;
+
+        if !content.is_empty() {
+            return Err(content.error("unexpected token"));
+        }
+
+        Ok(Self {
+            cast: Some((cast, as_)),
+            expression: Box::new(expression),
+            ty,
+        })
+    }
+}
+
+impl TypeCast {
+    fn parse_shorthand(input: ParseStream, expression: Expression) -> syn::Result<Self> {
+        input.parse::<Token![::]>()?;
+
+        Ok(Self {
+            cast: None,
+            expression: Box::new(expression),
+            ty: input.parse()?,
+        })
+    }
+
+    /// The target type, untyped values are read as it and the nullability is kept.
+    fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<ColumnType> {
+        let target = ColumnType::new(self.ty.sql_type()?, false);
+        let ty = self.expression.infer(scope, params, Some(&target))?;
+
+        Ok(target.with_nullable(ty.nullable))
+    }
+
+    fn span(&self) -> Span {
+        match &self.cast {
+            Some(_) => self.ty.ident.span(),
+            None => self.expression.span(),
+        }
+    }
+}
+
+impl SqlDisplay for TypeCast {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match &self.cast {
+            Some((cast, as_)) => {
+                cast.fmt_sql(f, level)?;
+                write!(f, "(")?;
+                self.expression.fmt_sql(f, level)?;
+                write!(f, " ")?;
+                as_.fmt_sql(f, level)?;
+                write!(f, " ")?;
+                self.ty.fmt_sql(f, level)?;
+                write!(f, ")")
+            }
+            None => {
+                self.expression.fmt_sql(f, level)?;
+                write!(f, "::")?;
+                self.ty.fmt_sql(f, level)
+            }
+        }
+    }
+}
+
+/// The target type of a cast, written like in SQL.
+///
+/// https://www.postgresql.org/docs/current/datatype.html
+#[derive(Debug)]
+pub struct TypeName {
+    /// as written, e.g. `double precision`
+    name: String,
+    modifiers: Vec<syn::LitInt>,
+    /// `[]` after the name
+    array: bool,
+    ident: syn::Ident,
+}
+
+// SQL type names and the diesel sql types they are named after in `schema.rs`
+const TYPE_NAMES: &[(&str, &str)] = &[
+    ("bigint", "Int8"),
+    ("bool", "Bool"),
+    ("boolean", "Bool"),
+    ("bpchar", "Bpchar"),
+    ("char", "Bpchar"),
+    ("character", "Bpchar"),
+    ("character varying", "Varchar"),
+    ("decimal", "Numeric"),
+    ("double precision", "Float8"),
+    ("float4", "Float4"),
+    ("float8", "Float8"),
+    ("int", "Int4"),
+    ("int2", "Int2"),
+    ("int4", "Int4"),
+    ("int8", "Int8"),
+    ("integer", "Int4"),
+    ("json", "Json"),
+    ("jsonb", "Jsonb"),
+    ("numeric", "Numeric"),
+    ("real", "Float4"),
+    ("smallint", "Int2"),
+    ("text", "Text"),
+    ("varchar", "Varchar"),
+];
+
+impl Parse for TypeName {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = syn::Ident::parse_any(input)?;
+        let mut name = ident.to_string();
+
+        // the only type names of two words
+        let second = match name.to_lowercase().as_str() {
+            "double" => Some("precision"),
+            "character" => Some("varying"),
+            _ => None,
+        };
+
+        if let Some(second) = second {
+            if matches!(input.cursor().ident(), Some((next, _)) if next.to_string().eq_ignore_ascii_case(second))
+            {
+                let next = syn::Ident::parse_any(input)?;
+                name = format!("{} {}", name, next);
+            }
+        }
+
+        let modifiers = if input.peek(Paren) {
+            let content;
+            parenthesized!(content in input);
+            content
+                .parse_terminated::<_, Token![,]>(|input| input.parse::<syn::LitInt>())?
+                .into_iter()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let array = if input.peek(Bracket) {
+            let content;
+            bracketed!(content in input);
+            if !content.is_empty() {
+                return Err(content.error("expected `[]`"));
+            }
+
+            true
+        } else {
+            false
+        };
+
+        Ok(Self {
+            name,
+            modifiers,
+            array,
+            ident,
+        })
+    }
+}
+
+impl TypeName {
+    fn sql_type(&self) -> syn::Result<SqlType> {
+        TYPE_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&self.name))
+            .map(|(_, ty)| SqlType::named(ty))
+            .map(|ty| if self.array { ty.array() } else { ty })
+            .ok_or_else(|| {
+                unknown_error(
+                    &self.ident,
+                    format!("unknown type `{}`", self.name),
+                    TYPE_NAMES.iter().map(|(name, _)| name.to_string()),
+                )
+            })
+    }
+}
+
+impl SqlDisplay for TypeName {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, _level: usize) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if !self.modifiers.is_empty() {
+            let modifiers: Vec<_> = self.modifiers.iter().map(|m| m.base10_digits()).collect();
+            write!(f, "({})", modifiers.join(", "))?;
+        }
+
+        if self.array {
+            write!(f, "[]")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An expression in parentheses.
+#[derive(Debug)]
+pub struct Nested {
+    paren: Paren,
+    pub expression: Box<Expression>,
+}
+
+impl Parse for Nested {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (paren, expression) = parse_parenthesized(input)?;
+        Ok(Self {
+            paren,
+            expression: Box::new(expression),
+        })
+    }
+}
+
+impl SqlDisplay for Nested {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "(")?;
+        self.expression.fmt_sql(f, level)?;
+        write!(f, ")")
+    }
+}
+
+/// The operators of binary expressions, with their precedence.
+///
+/// https://www.postgresql.org/docs/current/functions.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Concat,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    JsonGet,
+    JsonGetText,
+    JsonPath,
+    JsonPathText,
+    Contains,
+    ContainedBy,
+    HasKey,
+    HasAnyKey,
+    HasAllKeys,
+    Like,
+    NotLike,
+    ILike,
+    NotILike,
+    SimilarTo,
+    NotSimilarTo,
+    Match,
+    MatchInsensitive,
+    NotMatch,
+    NotMatchInsensitive,
+    TextSearch,
+}
+
+// words are separated by a space
+const KEYWORD_OPERATORS: &[(&str, BinaryOperator)] = &[
+    ("OR", BinaryOperator::Or),
+    ("AND", BinaryOperator::And),
+    ("LIKE", BinaryOperator::Like),
+    ("NOT LIKE", BinaryOperator::NotLike),
+    ("ILIKE", BinaryOperator::ILike),
+    ("NOT ILIKE", BinaryOperator::NotILike),
+    ("SIMILAR TO", BinaryOperator::SimilarTo),
+    ("NOT SIMILAR TO", BinaryOperator::NotSimilarTo),
+];
+
+// longer operators first, so `<=` isn't taken for `<`
+const PUNCT_OPERATORS: &[(&str, BinaryOperator)] = &[
+    ("->>", BinaryOperator::JsonGetText),
+    ("->", BinaryOperator::JsonGet),
+    ("#>>", BinaryOperator::JsonPathText),
+    ("#>", BinaryOperator::JsonPath),
+    ("@>", BinaryOperator::Contains),
+    ("<@", BinaryOperator::ContainedBy),
+    ("?|", BinaryOperator::HasAnyKey),
+    ("?&", BinaryOperator::HasAllKeys),
+    ("?", BinaryOperator::HasKey),
+    ("!~*", BinaryOperator::NotMatchInsensitive),
+    ("!~", BinaryOperator::NotMatch),
+    ("~*", BinaryOperator::MatchInsensitive),
+    ("~", BinaryOperator::Match),
+    ("@@", BinaryOperator::TextSearch),
+    ("<>", BinaryOperator::NotEqual),
+    ("!=", BinaryOperator::NotEqual),
+    ("<=", BinaryOperator::LessEqual),
+    (">=", BinaryOperator::GreaterEqual),
+    ("||", BinaryOperator::Concat),
+    ("<", BinaryOperator::Less),
+    (">", BinaryOperator::Greater),
+    ("=", BinaryOperator::Equal),
+    ("+", BinaryOperator::Add),
+    ("-", BinaryOperator::Subtract),
+    ("*", BinaryOperator::Multiply),
+    ("/", BinaryOperator::Divide),
+    ("%", BinaryOperator::Modulo),
+];
+
+impl Parse for BinaryOperator {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        for (keywords, op) in KEYWORD_OPERATORS {
+            let parsed = try_parse(input, |input| {
+                keywords
+                    .split(' ')
+                    .try_for_each(|keyword| parse_keyword(input, keyword).map(|_| ()))
+            });
+
+            if parsed.is_ok() {
+                return Ok(*op);
+            }
+        }
+
+        for (punct, op) in PUNCT_OPERATORS {
+            if parse_punct(input, punct).is_ok() {
+                return Ok(*op);
+            }
+        }
+
+        Err(input.error("expected operator"))
+    }
+}
+
+impl BinaryOperator {
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Equal
+            | Self::NotEqual
+            | Self::Less
+            | Self::LessEqual
+            | Self::Greater
+            | Self::GreaterEqual => 5,
+            Self::Like
+            | Self::NotLike
+            | Self::ILike
+            | Self::NotILike
+            | Self::SimilarTo
+            | Self::NotSimilarTo => PRECEDENCE_IN,
+            // "any other operator"
+            Self::Concat
+            | Self::JsonGet
+            | Self::JsonGetText
+            | Self::JsonPath
+            | Self::JsonPathText
+            | Self::Contains
+            | Self::ContainedBy
+            | Self::HasKey
+            | Self::HasAnyKey
+            | Self::HasAllKeys
+            | Self::Match
+            | Self::MatchInsensitive
+            | Self::NotMatch
+            | Self::NotMatchInsensitive
+            | Self::TextSearch => 7,
+            Self::Add | Self::Subtract => 8,
+            Self::Multiply | Self::Divide | Self::Modulo => 9,
+        }
+    }
+
+    fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            Self::Equal
+                | Self::NotEqual
+                | Self::Less
+                | Self::LessEqual
+                | Self::Greater
+                | Self::GreaterEqual
+        )
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Or => "OR",
+            Self::And => "AND",
+            Self::Equal => "=",
+            Self::NotEqual => "<>",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::Concat => "||",
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Modulo => "%",
+            Self::JsonGet => "->",
+            Self::JsonGetText => "->>",
+            Self::JsonPath => "#>",
+            Self::JsonPathText => "#>>",
+            Self::Contains => "@>",
+            Self::ContainedBy => "<@",
+            Self::HasKey => "?",
+            Self::HasAnyKey => "?|",
+            Self::HasAllKeys => "?&",
+            Self::Like => "LIKE",
+            Self::NotLike => "NOT LIKE",
+            Self::ILike => "ILIKE",
+            Self::NotILike => "NOT ILIKE",
+            Self::SimilarTo => "SIMILAR TO",
+            Self::NotSimilarTo => "NOT SIMILAR TO",
+            Self::Match => "~",
+            Self::MatchInsensitive => "~*",
+            Self::NotMatch => "!~",
+            Self::NotMatchInsensitive => "!~*",
+            Self::TextSearch => "@@",
+        }
+    }
+
+    fn infer(
+        self,
+        left: &Expression,
+        right: &Expression,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<ColumnType> {
+        match self {
+            Self::Or | Self::And => {
+                let left = left.infer_condition(scope, params)?;
+                let right = right.infer_condition(scope, params)?;
+                Ok(ColumnType::new(
+                    SqlType::named("Bool"),
+                    left.nullable || right.nullable,
+                ))
+            }
+            Self::Concat => {
+                // untyped operands are text, the other side may be of any type
+                let text = ColumnType::not_null("Text");
+                let mut left_ty = left.infer(scope, params, None)?;
+                let right_ty = right.infer(scope, params, Some(&text))?;
+                if left_ty.ty == SqlType::Unknown {
+                    left_ty = left.infer(scope, params, Some(&text))?;
+                }
+
+                if !left_ty.ty.is_compatible(&text.ty) && !right_ty.ty.is_compatible(&text.ty) {
+                    return Err(self.no_such_operator(left, &left_ty, &right_ty));
+                }
+
+                Ok(text.with_nullable(left_ty.nullable || right_ty.nullable))
+            }
+            Self::Add | Self::Subtract | Self::Multiply | Self::Divide | Self::Modulo => {
+                let (left_ty, right_ty) = infer_pair(left, right, scope, params)?;
+                let signatures = builtin::arithmetic_signatures(self.as_str());
+                let nullable = left_ty.nullable || right_ty.nullable;
+
+                match builtin::resolve(&signatures, &[left_ty.clone(), right_ty.clone()]) {
+                    Resolution::Found(signature) => {
+                        Ok(ColumnType::new(SqlType::named(signature.result), nullable))
+                    }
+                    Resolution::Ambiguous => Ok(ColumnType::new(SqlType::Unknown, nullable)),
+                    Resolution::NotFound => Err(self.no_such_operator(left, &left_ty, &right_ty)),
+                }
+            }
+            // a missing key or index results in `NULL`
+            Self::JsonGet | Self::JsonGetText | Self::JsonPath | Self::JsonPathText => {
+                let text = SqlType::named("Text");
+                let key = match self {
+                    Self::JsonGet | Self::JsonGetText => text.clone(),
+                    _ => text.array(),
+                };
+
+                let left_ty = left.infer(scope, params, None)?;
+                let right_ty =
+                    right.infer(scope, params, Some(&ColumnType::new(key.clone(), false)))?;
+                let index = matches!(self, Self::JsonGet | Self::JsonGetText)
+                    && right_ty.ty.is_compatible(&SqlType::named("Int4"));
+                if !left_ty.ty.is_json() || !(right_ty.ty.is_compatible(&key) || index) {
+                    return Err(self.no_such_operator(left, &left_ty, &right_ty));
+                }
+
+                Ok(match self {
+                    Self::JsonGet | Self::JsonPath => ColumnType::new(left_ty.ty, true),
+                    _ => ColumnType::new(text, true),
+                })
+            }
+            // only `jsonb` and arrays have containment
+            Self::Contains | Self::ContainedBy => {
+                let (left_ty, right_ty) = infer_pair(left, right, scope, params)?;
+                let jsonb = SqlType::named("Jsonb");
+                let valid = match left_ty.ty.element() {
+                    Some(_) => left_ty.ty == right_ty.ty,
+                    None => left_ty.ty == jsonb && right_ty.ty == jsonb,
+                };
+                if !valid {
+                    return Err(self.no_such_operator(left, &left_ty, &right_ty));
+                }
+
+                Ok(ColumnType::new(
+                    SqlType::named("Bool"),
+                    left_ty.nullable || right_ty.nullable,
+                ))
+            }
+            Self::Like
+            | Self::NotLike
+            | Self::ILike
+            | Self::NotILike
+            | Self::SimilarTo
+            | Self::NotSimilarTo
+            | Self::Match
+            | Self::MatchInsensitive
+            | Self::NotMatch
+            | Self::NotMatchInsensitive => {
+                let text = ColumnType::not_null("Text");
+                let left_ty = left.infer(scope, params, Some(&text))?;
+                let right_ty = right.infer(scope, params, Some(&text))?;
+                if !left_ty.ty.is_compatible(&text.ty) || !right_ty.ty.is_compatible(&text.ty) {
+                    return Err(self.no_such_operator(left, &left_ty, &right_ty));
+                }
+
+                // regular expressions match anywhere without wildcards
+                let wildcards = matches!(
+                    self,
+                    Self::Like
+                        | Self::NotLike
+                        | Self::ILike
+                        | Self::NotILike
+                        | Self::SimilarTo
+                        | Self::NotSimilarTo
+                );
+                if wildcards {
+                    if let Some(parameter) = concatenated_parameter(right) {
+                        params.warn(
+                            parameter.dollar.span,
+                            format!(
+                                "parameter ${} is concatenated into a {} pattern unescaped, so `%` and `_` in it are wildcards, escape them with `replace`",
+                                parameter.index,
+                                self.as_str()
+                            ),
+                        );
+                    }
+                }
+
+                Ok(ColumnType::new(
+                    SqlType::named("Bool"),
+                    left_ty.nullable || right_ty.nullable,
+                ))
+            }
+            // untyped operands are text, which Postgres converts to a `tsvector` or `tsquery`
+            Self::TextSearch => {
+                let text = ColumnType::not_null("Text");
+                let mut left_ty = left.infer(scope, params, None)?;
+                let right_ty = right.infer(scope, params, Some(&text))?;
+                if left_ty.ty == SqlType::Unknown {
+                    left_ty = left.infer(scope, params, Some(&text))?;
+                }
+
+                let is = |ty: &ColumnType, name: &str| ty.ty == SqlType::named(name);
+                let is_text = |ty: &ColumnType| ty.ty.is_compatible(&text.ty);
+                let valid = (is(&left_ty, "TsVector") && is(&right_ty, "TsQuery"))
+                    || (is(&left_ty, "TsQuery") && is(&right_ty, "TsVector"))
+                    || (is_text(&left_ty) && (is(&right_ty, "TsQuery") || is_text(&right_ty)));
+                if !valid {
+                    return Err(self.no_such_operator(left, &left_ty, &right_ty));
+                }
+
+                Ok(ColumnType::new(
+                    SqlType::named("Bool"),
+                    left_ty.nullable || right_ty.nullable,
+                ))
+            }
+            Self::HasKey | Self::HasAnyKey | Self::HasAllKeys => {
+                let text = SqlType::named("Text");
+                let key = match self {
+                    Self::HasKey => text,
+                    _ => text.array(),
+                };
+
+                let left_ty = left.infer(scope, params, None)?;
+                let right_ty =
+                    right.infer(scope, params, Some(&ColumnType::new(key.clone(), false)))?;
+                if left_ty.ty != SqlType::named("Jsonb") || !right_ty.ty.is_compatible(&key) {
+                    return Err(self.no_such_operator(left, &left_ty, &right_ty));
+                }
+
+                Ok(ColumnType::new(
+                    SqlType::named("Bool"),
+                    left_ty.nullable || right_ty.nullable,
+                ))
+            }
+            _ => {
+                let (left, right) = infer_operands(left, right, scope, params)?;
+                Ok(ColumnType::new(
+                    SqlType::named("Bool"),
+                    left.nullable || right.nullable,
+                ))
+            }
+        }
+    }
+
+    fn no_such_operator(self, left: &Expression, l: &ColumnType, r: &ColumnType) -> syn::Error {
+        syn::Error::new(
+            left.span(),
+            format!(
+                "operator does not exist: {} {} {}",
+                l.ty,
+                self.as_str(),
+                r.ty
+            ),
+        )
+    }
+}
+
+/// A parameter within a `||` concatenation of a pattern, e.g. `'%' || $1 || '%'`, unless it's
+/// passed through a function like `replace`.
+fn concatenated_parameter(pattern: &Expression) -> Option<&Parameter> {
+    fn parameter(expression: &Expression) -> Option<&Parameter> {
+        match expression {
+            Expression::Parameter(parameter) => Some(parameter),
+            Expression::Cast(cast) => parameter(&cast.expression),
+            Expression::Nested(nested) => parameter(&nested.expression),
+            _ => None,
+        }
+    }
+
+    match pattern {
+        Expression::Binary(left, BinaryOperator::Concat, right) => parameter(left)
+            .or_else(|| parameter(right))
+            .or_else(|| concatenated_parameter(left))
+            .or_else(|| concatenated_parameter(right)),
+        Expression::Nested(nested) => concatenated_parameter(&nested.expression),
+        _ => None,
+    }
+}
+
+/// Infers both operands, each side types an untyped other side.
+fn infer_pair(
+    left: &Expression,
+    right: &Expression,
+    scope: &Scope,
+    params: &mut Params,
+) -> syn::Result<(ColumnType, ColumnType)> {
+    let mut left_ty = left.infer(scope, params, None)?;
+    let right_ty = right.infer(scope, params, known(&left_ty).as_ref())?;
+    if left_ty.ty == SqlType::Unknown {
+        left_ty = left.infer(scope, params, known(&right_ty).as_ref())?;
+    }
+
+    Ok((left_ty, right_ty))
+}
+
+/// Infers both sides of a comparison, each side types an untyped other side.
+fn infer_operands(
+    left: &Expression,
+    right: &Expression,
+    scope: &Scope,
+    params: &mut Params,
+) -> syn::Result<(ColumnType, ColumnType)> {
+    let (left_ty, right_ty) = infer_pair(left, right, scope, params)?;
+    if !left_ty.ty.is_compatible(&right_ty.ty) {
+        return Err(syn::Error::new(
+            right.span(),
+            format!("cannot compare {} with {}", left_ty.ty, right_ty.ty),
+        ));
+    }
+
+    Ok((left_ty, right_ty))
+}
+
+/// The type an operand passes to the other side, operators never bind `NULL`.
+fn known(ty: &ColumnType) -> Option<ColumnType> {
+    match ty.ty {
+        SqlType::Unknown => None,
+        _ => Some(ty.with_nullable(false)),
+    }
+}
+
+/// `IS NULL` and `IS NOT NULL`, which are never null themselves.
+///
+/// https://www.postgresql.org/docs/current/functions-comparison.html
+#[derive(Debug)]
+pub struct IsNull {
+    is: Is,
+    not: Option<Not>,
+    null: Null,
+}
+
+impl Parse for IsNull {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                is: input.parse()?,
+                not: input.parse().ok(),
+                null: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for IsNull {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.is.fmt_sql(f, level)?;
+        write!(f, " ")?;
+
+        if let Some(not) = &self.not {
+            not.fmt_sql(f, level)?;
+            write!(f, " ")?;
+        }
+
+        self.null.fmt_sql(f, level)
+    }
+}
+
+/// A parenthesized query used as a value, it returns one column and at most one row.
+///
+/// https://www.postgresql.org/docs/current/sql-expressions.html#SQL-SYNTAX-SCALAR-SUBQUERIES
+#[derive(Debug)]
+pub struct Subquery {
+    paren: Paren,
+    query: Box<Query>,
+}
+
+/// Whether the input is a parenthesized query rather than a nested expression.
+fn peek_subquery(input: ParseStream) -> bool {
+    match input.cursor().group(proc_macro2::Delimiter::Parenthesis) {
+        Some((inner, _, _)) => {
+            matches!(inner.ident(), Some((ident, _)) if ident == "SELECT" || ident == "WITH")
+        }
+        None => false,
+    }
+}
+
+impl Parse for Subquery {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (paren, query) = parse_parenthesized(input)?;
+        Ok(Self {
+            paren,
+            query: Box::new(query),
+        })
+    }
+}
+
+impl Subquery {
+    /// The type of the only column, the outer query's tables are visible within the subquery.
+    fn column(&self, scope: &Scope, params: &mut Params) -> syn::Result<ColumnType> {
+        match self.query.columns(scope, params)?.as_slice() {
+            [column] => Ok(column.ty.clone()),
+            _ => Err(syn::Error::new(
+                self.paren.span,
+                "subquery must return only one column",
+            )),
+        }
+    }
+}
+
+impl SqlDisplay for Subquery {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        writeln!(f, "(")?;
+        self.query.fmt_sql(f, level + 1)?;
+        writeln!(f)?;
+        indent(f, level)?;
+        write!(f, ")")
+    }
+}
+
+/// `EXISTS (SELECT ...)`, true if the subquery returns any rows.
+///
+/// https://www.postgresql.org/docs/current/functions-subquery.html#FUNCTIONS-SUBQUERY-EXISTS
+#[derive(Debug)]
+pub struct Exists {
+    span: Span,
+    subquery: Subquery,
+}
+
+impl Parse for Exists {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            span: parse_keyword(input, "EXISTS")?,
+            subquery: input.parse()?,
+        })
+    }
+}
+
+impl SqlDisplay for Exists {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "EXISTS ")?;
+        self.subquery.fmt_sql(f, level)
+    }
+}
+
+/// `[NOT] IN (SELECT ...)`, compares a value with each row of the subquery.
+///
+/// https://www.postgresql.org/docs/current/functions-subquery.html#FUNCTIONS-SUBQUERY-IN
+#[derive(Debug)]
+pub struct InSubquery {
+    not: Option<Not>,
+    in_: In,
+    subquery: Subquery,
+}
+
+impl InSubquery {
+    /// Parses `[NOT] IN (SELECT ...)`, errors after `IN` aren't swallowed.
+    fn parse_optional(input: ParseStream) -> syn::Result<Option<Self>> {
+        let (not, in_) = match try_parse(input, |input| Ok((input.parse().ok(), input.parse()?))) {
+            Ok(not_in) => not_in,
+            Err(_) => return Ok(None),
+        };
+
+        if !peek_subquery(input) {
+            return Err(input.error("expected a subquery, e.g. `IN (SELECT ...)`"));
+        }
+
+        Ok(Some(Self {
+            not,
+            in_,
+            subquery: input.parse()?,
+        }))
+    }
+
+    /// Like a comparison with each row, the subquery types an untyped left side.
+    fn infer(
+        &self,
+        left: &Expression,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<ColumnType> {
+        let mut left_ty = left.infer(scope, params, None)?;
+        let mut column = self.subquery.column(scope, params)?;
+        if left_ty.ty == SqlType::Unknown {
+            left_ty = left.infer(scope, params, known(&column).as_ref())?;
+        } else if column.ty == SqlType::Unknown {
+            if let Some(ty) =
+                self.subquery
+                    .query
+                    .infer_column_as(scope, params, &left_ty.with_nullable(false))?
+            {
+                column = ty;
+            }
+        }
+
+        if !left_ty.ty.is_compatible(&column.ty) {
+            return Err(syn::Error::new(
+                self.subquery.paren.span,
+                format!("cannot compare {} with {}", left_ty.ty, column.ty),
+            ));
+        }
+
+        Ok(ColumnType::new(
+            SqlType::named("Bool"),
+            left_ty.nullable || column.nullable,
+        ))
+    }
+}
+
+impl SqlDisplay for InSubquery {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        if let Some(not) = &self.not {
+            not.fmt_sql(f, level)?;
+            write!(f, " ")?;
+        }
+
+        self.in_.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.subquery.fmt_sql(f, level)
+    }
+}
+
+/// `ANY (array)` or `ALL (array)`, compares a value with each element of an array.
+///
+/// https://www.postgresql.org/docs/current/functions-comparisons.html#FUNCTIONS-COMPARISONS-ANY-SOME
+#[derive(Debug)]
+pub struct ArrayComparison {
+    quantifier: EitherAnyOrAll,
+    paren: Paren,
+    array: Box<Expression>,
+}
+
+impl Parse for ArrayComparison {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let quantifier = input.parse()?;
+        if peek_subquery(input) {
+            return Err(
+                input.error("`ANY` and `ALL` take an array, use `IN (SELECT ...)` for a subquery")
+            );
+        }
+
+        let (paren, array) = parse_parenthesized(input)?;
+        Ok(Self {
+            quantifier,
+            paren,
+            array: Box::new(array),
+        })
+    }
+}
+
+impl ArrayComparison {
+    fn peek(input: ParseStream) -> bool {
+        (peek_keyword(input, "ANY") || peek_keyword(input, "ALL")) && input.peek2(Paren)
+    }
+
+    /// Compares with each element, the left side types an untyped array and vice versa.
+    fn infer(
+        &self,
+        left: &Expression,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<ColumnType> {
+        let mut left_ty = left.infer(scope, params, None)?;
+        let expected = known(&left_ty).map(|ty| ColumnType::new(ty.ty.array(), false));
+        let array = self.array.infer(scope, params, expected.as_ref())?;
+
+        match array.ty.element() {
+            Some(element) => {
+                if left_ty.ty == SqlType::Unknown {
+                    left_ty = left.infer(
+                        scope,
+                        params,
+                        Some(&ColumnType::new(element.clone(), false)),
+                    )?;
+                }
+
+                if !left_ty.ty.is_compatible(&element) {
+                    return Err(syn::Error::new(
+                        self.paren.span,
+                        format!("cannot compare {} with {}", left_ty.ty, element),
+                    ));
+                }
+            }
+            // both sides untyped, the parameters can't be inferred
+            None if array.ty == SqlType::Unknown => {}
+            None => {
+                return Err(syn::Error::new(
+                    self.paren.span,
+                    format!("`ANY` and `ALL` require an array, found {}", array.ty),
+                ))
+            }
+        }
+
+        Ok(ColumnType::new(
+            SqlType::named("Bool"),
+            left_ty.nullable || array.nullable,
+        ))
+    }
+}
+
+impl SqlDisplay for ArrayComparison {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.quantifier.fmt_sql(f, level)?;
+        write!(f, " (")?;
+        self.array.fmt_sql(f, level)?;
+        write!(f, ")")
+    }
+}
+
+impl_token_ident!(Cast, "CAST");
+impl_token_ident!(When, "WHEN");
+impl_token_ident!(Then, "THEN");
+impl_token_ident!(Else, "ELSE");
+impl_token_ident!(End, "END");
+impl_token_ident!(pub, Not, "NOT");
+impl_token_ident!(Is, "IS");
+impl_token_ident!(Null, "NULL");
+impl_token_ident!(In, "IN");
+impl_token_ident!(pub, Over, "OVER");
+impl_token_ident!(Partition, "PARTITION");
+impl_token_ident!(Any, "ANY");
+
+impl_either!(Any, All);
@@ -1,16 +1,26 @@
 use self::{
-    schema::{EitherNullableTypeOrIdentifier, Schema},
-    sql::SqlStatement,
+    schema::Schema,
+    scope::{ColumnType, Params, SqlType},
+    sql::{Cardinality, Projection, SqlStatement},
 };
 use proc_macro::{Span, TokenStream};
 use quote::{format_ident, quote};
 use syn::{
     ext::IdentExt,
+    parenthesized,
     parse::{discouraged::Speculative, Parse},
+    parse_quote,
+    token::Paren,
 };
 
+mod builtin;
+mod dml;
+mod expr;
 mod schema;
+mod scope;
 mod sql;
+#[cfg(test)]
+mod tests;
 
 use sql::ProjectionWalker;
 
@@ -29,9 +39,18 @@ impl Parse for Sql {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let statement: SqlStatement = input.parse()?;
 
-        let path = Span::call_site().source_file().path();
-        let path = path.parent().unwrap();
-        let path = path.join(statement.file.value());
+        // the schema file is relative to the file containing the macro call
+        let path = Span::call_site()
+            .local_file()
+            .as_deref()
+            .and_then(std::path::Path::parent)
+            .map(|dir| dir.join(statement.file.value()))
+            .ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &statement.file,
+                    "cannot locate the schema file, the macro call has no source file",
+                )
+            })?;
 
         let schema = match std::fs::read_to_string(path) {
             Ok(string) => string,
@@ -50,64 +69,284 @@ impl Parse for Sql {
     }
 }
 
-fn sql_name_to_id(name: &String) -> String {
+fn sql_name_to_id(name: &str) -> String {
     name.chars()
-        .into_iter()
         .map(|c| if c == '.' { '_' } else { c })
         .collect()
 }
 
-fn sql_type_to_ty(name: &String) -> String {
-    match name.as_str() {
-        "Int4" => "i32",
-        "Text" => "String",
-        "Float8" => "f32",
-        x => panic!("unknown type: {}", x),
+fn sql_type_to_ty(ty: &SqlType, span: proc_macro2::Span) -> syn::Result<syn::Type> {
+    let name = match ty {
+        SqlType::Unknown => {
+            return Err(syn::Error::new(span, "cannot infer the type of this value"))
+        }
+        SqlType::Named(name) => name,
+    };
+
+    if let Some(element) = ty.element() {
+        let element = sql_type_to_ty(&element, span)?;
+        return Ok(parse_quote! { ::std::vec::Vec<#element> });
+    }
+
+    Ok(match name.as_str() {
+        "Int2" => parse_quote! { i16 },
+        "Int4" => parse_quote! { i32 },
+        "Int8" => parse_quote! { i64 },
+        "Text" | "Varchar" | "Bpchar" => parse_quote! { String },
+        "Float4" => parse_quote! { f32 },
+        "Float8" => parse_quote! { f64 },
+        // requires `rust_decimal` with the `db-postgres` feature
+        "Numeric" => parse_quote! { ::rust_decimal::Decimal },
+        "Bool" => parse_quote! { bool },
+        // requires `serde_json` and the `with-serde_json-1` feature of `postgres`
+        "Json" | "Jsonb" => parse_quote! { ::serde_json::Value },
+        x => {
+            return Err(syn::Error::new(
+                span,
+                format!("the SQL type `{}` has no Rust type", x),
+            ))
+        }
+    })
+}
+
+fn column_type_to_ty(
+    ty: &ColumnType,
+    span: proc_macro2::Span,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let t = sql_type_to_ty(&ty.ty, span)?;
+
+    if ty.nullable {
+        Ok(quote! {
+            ::std::option::Option<#t>
+        })
+    } else {
+        Ok(quote! {
+            #t
+        })
     }
-    .to_owned()
 }
 
-impl std::convert::Into<TokenStream> for Sql {
-    fn into(self) -> TokenStream {
+fn sql_type_to_param_ty(
+    ty: &SqlType,
+    span: proc_macro2::Span,
+) -> syn::Result<proc_macro2::TokenStream> {
+    match ty {
+        SqlType::Named(name) if matches!(name.as_str(), "Text" | "Varchar" | "Bpchar") => {
+            Ok(quote! { &str })
+        }
+        _ => {
+            let t = sql_type_to_ty(ty, span)?;
+            Ok(quote! { #t })
+        }
+    }
+}
+
+fn column_type_to_param_ty(
+    ty: &ColumnType,
+    span: proc_macro2::Span,
+) -> syn::Result<proc_macro2::TokenStream> {
+    // arrays are passed as slices, e.g. `&[i32]` for `$1::int[]`
+    let t = match ty.ty.element() {
+        Some(element) => {
+            let element = sql_type_to_param_ty(&element, span)?;
+            quote! { &[#element] }
+        }
+        None => sql_type_to_param_ty(&ty.ty, span)?,
+    };
+
+    if ty.nullable {
+        Ok(quote! {
+            ::std::option::Option<#t>
+        })
+    } else {
+        Ok(t)
+    }
+}
+
+/// The struct fields and their initializers from the row.
+fn projections_to_fields(
+    projections: &[Projection],
+) -> syn::Result<(Vec<proc_macro2::TokenStream>, Vec<proc_macro2::TokenStream>)> {
+    let mut fields = Vec::new();
+    let mut mapper = Vec::new();
+    let mut names = Vec::new();
+
+    for (i, projection) in projections.iter().enumerate() {
+        let name = projection.name.as_ref().ok_or_else(|| {
+            syn::Error::new(projection.span, "expression needs a name, add `AS name`")
+        })?;
+
+        // e.g. `p.name` and `t.name` of a join
+        let id = sql_name_to_id(name);
+        if names.contains(&id) {
+            return Err(syn::Error::new(
+                projection.span,
+                format!(
+                    "column `{}` is selected more than once, add an alias with `AS name`",
+                    id
+                ),
+            ));
+        }
+        names.push(id.clone());
+
+        if projection.ty.ty == SqlType::Unknown {
+            return Err(syn::Error::new(
+                projection.span,
+                format!("cannot infer the type of column `{}`", name),
+            ));
+        }
+
+        let name = format_ident!("{}", id);
+
+        // json is decoded with the `Deserialize` implementation of the type
+        if let Some(json) = &projection.json {
+            if projection.ty.nullable {
+                fields.push(quote! {
+                    pub #name: ::std::option::Option<#json>
+                });
+
+                mapper.push(quote! {
+                    #name: row.try_get::<_, ::std::option::Option<::postgres::types::Json<#json>>>(#i)?.map(|json| json.0)
+                });
+            } else {
+                fields.push(quote! {
+                    pub #name: #json
+                });
+
+                mapper.push(quote! {
+                    #name: row.try_get::<_, ::postgres::types::Json<#json>>(#i)?.0
+                });
+            }
+
+            continue;
+        }
+
+        let ty = column_type_to_ty(&projection.ty, projection.span)?;
+
+        fields.push(quote! {
+            pub #name: #ty
+        });
+
+        mapper.push(quote! {
+            #name: row.try_get(#i)?
+        });
+    }
+
+    Ok((fields, mapper))
+}
+
+impl From<Sql> for TokenStream {
+    fn from(sql: Sql) -> Self {
+        sql.expand().into()
+    }
+}
+
+impl Sql {
+    /// The result struct of the statement and its query or execute function.
+    fn expand(self) -> proc_macro2::TokenStream {
         let s = format!("{}", self.statement);
         let name = &self.statement.name;
 
-        let mut fields = Vec::new();
-        let mut mapper = Vec::new();
-        let mut i = 0usize;
+        let mut params = Params::default();
+        let projections = self.statement.projections(&self.schema, &mut params);
 
-        self.statement.walk_projection(&self.schema, &mut |proj| {
-            let name = sql_name_to_id(&proj.name);
+        for (span, message) in params.take_warnings() {
+            warn(span, &message);
+        }
 
-            let ty = match proj.diesel_type {
-                EitherNullableTypeOrIdentifier::NullableType(ty) => {
-                    let t = sql_type_to_ty(&(ty.get_type_name()));
-                    let t = format_ident!("{}", t);
-                    quote! {
-                        ::std::option::Option<#t>
-                    }
+        let (projections, params) = match projections.and_then(|p| Ok((p, params.finish()?))) {
+            Ok(walked) => walked,
+            Err(err) => return err.to_compile_error(),
+        };
+
+        let (fields, mapper) = match projections_to_fields(&projections) {
+            Ok(fields) => fields,
+            Err(err) => return err.to_compile_error(),
+        };
+
+        let cardinality = match self.statement.cardinality(&self.schema) {
+            Ok(cardinality) => cardinality,
+            Err(err) => return err.to_compile_error(),
+        };
+
+        let param_names: Vec<_> = (1..=params.len())
+            .map(|i| format_ident!("p{}", i))
+            .collect();
+        let param_types = match params
+            .iter()
+            .map(|(span, ty)| column_type_to_param_ty(ty, *span))
+            .collect::<syn::Result<Vec<_>>>()
+        {
+            Ok(param_types) => param_types,
+            Err(err) => return err.to_compile_error(),
+        };
+        let param_count = params.len();
+
+        // the default cardinality calls one of the other functions
+        let query = match cardinality {
+            Cardinality::Many => quote! {
+                pub fn query(client: &mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<Vec<#name>, ::postgres::Error> {
+                    let result = client.query(#s, &[#(&#param_names),*])?;
+                    result.iter().map(Self::from_row).collect()
                 }
-                EitherNullableTypeOrIdentifier::Identifier(ty) => {
-                    let t = sql_type_to_ty(&(ty.id.to_string()));
-                    let t = format_ident!("{}", t);
-                    quote! {
-                        #t
-                    }
+            },
+            Cardinality::One => quote! {
+                pub fn query(client: &mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<#name, ::postgres::Error> {
+                    Self::query_one(client #(, #param_names)*)
                 }
-            };
+            },
+            Cardinality::Optional => quote! {
+                pub fn query(client: &mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<::std::option::Option<#name>, ::postgres::Error> {
+                    Self::query_opt(client #(, #param_names)*)
+                }
+            },
+        };
 
-            let name = format_ident!("{}", name);
+        let item = if self.statement.returns_rows() {
+            quote! {
+                #[derive(Debug)]
+                pub struct #name {
+                    #(#fields),*
+                }
 
-            fields.push(quote! {
-                pub #name: #ty
-            });
+                impl #name {
+                    #query
 
-            mapper.push(quote! {
-                #name: row.get(#i)
-            });
+                    pub fn query_one(client: &mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<#name, ::postgres::Error> {
+                        let row = client.query_one(#s, &[#(&#param_names),*])?;
+                        Self::from_row(&row)
+                    }
 
-            i += 1;
-        });
+                    pub fn query_opt(client: &mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<::std::option::Option<#name>, ::postgres::Error> {
+                        let row = client.query_opt(#s, &[#(&#param_names),*])?;
+                        row.map(|row| Self::from_row(&row)).transpose()
+                    }
+
+                    pub fn query_iter<'a>(client: &'a mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<impl ::postgres::fallible_iterator::FallibleIterator<Item = #name, Error = ::postgres::Error> + 'a, ::postgres::Error> {
+                        let params: [&dyn ::postgres::types::ToSql; #param_count] = [#(&#param_names),*];
+                        let rows = client.query_raw(#s, params)?;
+                        Ok(::postgres::fallible_iterator::FallibleIterator::map(rows, |row| Self::from_row(&row)))
+                    }
+
+                    fn from_row(row: &::postgres::Row) -> ::std::result::Result<#name, ::postgres::Error> {
+                        Ok(#name {
+                            #(#mapper),*
+                        })
+                    }
+                }
+            }
+        } else {
+            quote! {
+                #[derive(Debug)]
+                pub struct #name;
+
+                impl #name {
+                    pub fn execute(client: &mut ::postgres::Client #(, #param_names: #param_types)*) -> ::std::result::Result<u64, ::postgres::Error> {
+                        client.execute(#s, &[#(&#param_names),*])
+                    }
+                }
+            }
+        };
 
         quote! {
             #[doc = " SQL:"]
@@ -115,41 +354,87 @@ impl std::convert::Into<TokenStream> for Sql {
             #[doc = "```sql"]
             #[doc = #s]
             #[doc = "```"]
-            #[derive(Debug)]
-            pub struct #name {
-                #(#fields),*
-            }
-
-            impl #name {
-                pub fn query(client: &mut ::postgres::Client) -> ::std::result::Result<Vec<#name>, ::postgres::Error> {
-                    let result = client.query(#s, &[])?;
-                    let items: Vec<_> = result.iter().map(|row| {
-                        #name {
-                            #(#mapper),*
-                        }
-                    }).collect();
-
-                    Ok(items)
-                }
-            }
-      }.into()
+            #item
+        }
     }
 }
 
 //
 
-///
-///
-///
+/// An SQL identifier, which may also be a Rust keyword like `type`.
 #[derive(Debug)]
 struct Identifier {
     id: syn::Ident,
 }
 
+// keywords which end an expression or table reference, so they are no identifiers
+const RESERVED: &[&str] = &[
+    "ALL",
+    "AND",
+    "ANY",
+    "AS",
+    "CASE",
+    "CROSS",
+    "DEFAULT",
+    "DELETE",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "FETCH",
+    "FALSE",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NATURAL",
+    "NOT",
+    "NULL",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "RETURNING",
+    "RIGHT",
+    "SELECT",
+    "SET",
+    "SIMILAR",
+    "THEN",
+    "TRUE",
+    "UNION",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
 impl Parse for Identifier {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        Ok(Self {
-            id: syn::Ident::parse_any(input)?,
+        try_parse(input, |input| {
+            let id = syn::Ident::parse_any(input)?;
+            if RESERVED.contains(&id.to_string().as_str()) {
+                return Err(syn::Error::new(
+                    id.span(),
+                    format!("unexpected keyword `{}`", id),
+                ));
+            }
+
+            Ok(Self { id })
         })
     }
 }
@@ -162,6 +447,129 @@ impl SqlDisplay for Identifier {
 
 // helper
 
+fn warn(span: proc_macro2::Span, message: &str) {
+    proc_macro::Diagnostic::spanned(span.unwrap(), proc_macro::Level::Warning, message).emit();
+}
+
+fn unknown_error(
+    ident: &syn::Ident,
+    message: String,
+    candidates: impl IntoIterator<Item = String>,
+) -> syn::Error {
+    let message = match did_you_mean(&ident.to_string(), candidates) {
+        Some(suggestion) => format!("{}, did you mean `{}`?", message, suggestion),
+        None => message,
+    };
+
+    syn::Error::new(ident.span(), message)
+}
+
+fn did_you_mean(name: &str, candidates: impl IntoIterator<Item = String>) -> Option<String> {
+    // allow roughly one typo per three characters, but at least one
+    let max_distance = std::cmp::max(1, name.chars().count() / 3);
+
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            let distance = edit_distance(&name.to_lowercase(), &candidate.to_lowercase());
+            (distance, candidate)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            let cost = if ca == *cb { 0 } else { 1 };
+            row[j + 1] = (diagonal + cost).min(above + 1).min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+fn parse_parenthesized<T: Parse>(input: syn::parse::ParseStream) -> syn::Result<(Paren, T)> {
+    let content;
+    let paren = parenthesized!(content in input);
+    let inner = content.parse()?;
+
+    if !content.is_empty() {
+        return Err(content.error("unexpected token"));
+    }
+
+    Ok((paren, inner))
+}
+
+/// Renders a single node, e.g. to compare expressions.
+fn sql_string(node: &dyn SqlDisplay) -> String {
+    struct Display<'a>(&'a dyn SqlDisplay);
+
+    impl std::fmt::Display for Display<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            self.0.fmt_sql(f, 0)
+        }
+    }
+
+    Display(node).to_string()
+}
+
+fn peek_keyword(input: syn::parse::ParseStream, keyword: &str) -> bool {
+    matches!(input.cursor().ident(), Some((ident, _)) if ident == keyword)
+}
+
+/// Parses `T` if the input starts with `keyword`, so errors after the keyword aren't swallowed.
+fn parse_if_keyword<T: Parse>(
+    input: syn::parse::ParseStream,
+    keyword: &str,
+) -> syn::Result<Option<T>> {
+    if peek_keyword(input, keyword) {
+        Ok(Some(input.parse()?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_keyword(input: syn::parse::ParseStream, keyword: &str) -> syn::Result<proc_macro2::Span> {
+    input.step(|cursor| match cursor.ident() {
+        Some((ident, rest)) if ident == keyword => Ok((ident.span(), rest)),
+        _ => Err(cursor.error(format!("expected `{}`", keyword))),
+    })
+}
+
+/// Parses a multi-character operator like `<>`, whose characters must not be separated.
+fn parse_punct(input: syn::parse::ParseStream, punct: &str) -> syn::Result<proc_macro2::Span> {
+    input.step(|cursor| {
+        let mut rest = *cursor;
+        let mut span = None;
+        let len = punct.chars().count();
+
+        for (i, ch) in punct.chars().enumerate() {
+            match rest.punct() {
+                Some((p, next))
+                    if p.as_char() == ch
+                        && (i + 1 == len || p.spacing() == proc_macro2::Spacing::Joint) =>
+                {
+                    span.get_or_insert(p.span());
+                    rest = next;
+                }
+                _ => return Err(cursor.error(format!("expected `{}`", punct))),
+            }
+        }
+
+        Ok((span.expect("empty punct"), rest))
+    })
+}
+
 fn try_parse<T>(
     input: syn::parse::ParseStream,
     f: impl FnOnce(syn::parse::ParseStream) -> syn::Result<T>,
@@ -176,6 +584,8 @@ fn try_parse<T>(
     result
 }
 
+// helpers to call while debugging the parser
+#[allow(dead_code)]
 mod debug {
     use std::fmt::Debug;
     use syn::parse::{Parse, ParseStream};
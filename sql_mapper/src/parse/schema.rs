@@ -1,12 +1,6 @@
-use syn::{
-    braced, parenthesized,
-    parse::Parse,
-    punctuated::Punctuated,
-    token::{Brace, Paren},
-    Macro, Token,
-};
+use syn::{braced, parenthesized, parse::Parse, punctuated::Punctuated, Macro, Token};
 
-use super::{try_parse, SqlDisplay};
+use super::{try_parse, unknown_error, SqlDisplay};
 
 #[derive(Debug)]
 pub struct Schema {
@@ -36,44 +30,85 @@ impl Parse for Schema {
     }
 }
 
+impl Schema {
+    /// Finds a table ignoring case, like unquoted names in SQL.
+    pub fn table(&self, name: &syn::Ident) -> syn::Result<&TableDefinition> {
+        let lowercase = name.to_string().to_lowercase();
+
+        self.tables
+            .iter()
+            .find(|t| t.table.to_string().to_lowercase() == lowercase)
+            .ok_or_else(|| {
+                unknown_error(
+                    name,
+                    format!("unknown table `{}`", name),
+                    self.tables.iter().map(|t| t.table.to_string()),
+                )
+            })
+    }
+}
+
 #[derive(Debug)]
 pub struct TableDefinition {
     pub table: syn::Ident,
-    pub keys_paren: Paren,
     pub keys: Punctuated<syn::Ident, Token![,]>,
-    pub def_brace: Brace,
     pub def: Punctuated<FieldDefinition, Token![,]>,
 }
 
 impl Parse for TableDefinition {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         try_parse(input, |input| {
+            let table = input.parse()?;
+
             let keys;
+            parenthesized!(keys in input);
+            let keys = keys.parse_terminated(syn::Ident::parse)?;
+
             let def;
+            braced!(def in input);
+            let def = def.parse_terminated(FieldDefinition::parse)?;
 
-            Ok(Self {
-                table: input.parse()?,
-                keys_paren: parenthesized!(keys in input),
-                keys: keys.parse_terminated(syn::Ident::parse)?,
-                def_brace: braced!(def in input),
-                def: def.parse_terminated(FieldDefinition::parse)?,
-            })
+            let definition = Self { table, keys, def };
+            for key in &definition.keys {
+                definition.column(key)?;
+            }
+
+            Ok(definition)
         })
     }
 }
 
+impl TableDefinition {
+    /// Finds a column ignoring case, like [`Schema::table`].
+    pub fn column(&self, name: &syn::Ident) -> syn::Result<&FieldDefinition> {
+        let lowercase = name.to_string().to_lowercase();
+
+        self.def
+            .iter()
+            .find(|f| f.name.to_string().to_lowercase() == lowercase)
+            .ok_or_else(|| {
+                unknown_error(
+                    name,
+                    format!("unknown column `{}` in `{}`", name, self.table),
+                    self.def.iter().map(|f| f.name.to_string()),
+                )
+            })
+    }
+}
+
 #[derive(Debug)]
 pub struct FieldDefinition {
     pub name: syn::Ident,
-    pub arrow: (Token![-], Token![>]),
     pub ty: EitherNullableTypeOrIdentifier,
 }
 
 impl Parse for FieldDefinition {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![->]>()?;
+
         Ok(FieldDefinition {
-            name: input.parse()?,
-            arrow: (input.parse()?, input.parse()?),
+            name,
             ty: input.parse()?,
         })
     }
@@ -85,10 +120,7 @@ impl_token_ident!(Nullable, "Nullable");
 
 #[derive(Debug)]
 pub struct NullableType {
-    nullable: Nullable,
-    pub lt: Token![<],
     ty: syn::Ident,
-    pub gt: Token![>],
 }
 
 impl NullableType {
@@ -100,12 +132,12 @@ impl NullableType {
 impl Parse for NullableType {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         try_parse(input, |input| {
-            Ok(Self {
-                nullable: input.parse()?,
-                lt: input.parse()?,
-                ty: input.parse()?,
-                gt: input.parse()?,
-            })
+            input.parse::<Nullable>()?;
+            input.parse::<Token![<]>()?;
+            let ty = input.parse()?;
+            input.parse::<Token![>]>()?;
+
+            Ok(Self { ty })
         })
     }
 }
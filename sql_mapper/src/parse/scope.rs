@@ -0,0 +1,406 @@
+use super::{
+    schema::{EitherNullableTypeOrIdentifier, Schema, TableDefinition},
+    unknown_error,
+};
+use proc_macro2::Span;
+use std::collections::BTreeMap;
+
+// types
+
+/// Type of a value, named after the diesel sql types used in `schema.rs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlType {
+    /// e.g. `NULL` or a parameter without any context
+    Unknown,
+    Named(String),
+}
+
+impl SqlType {
+    pub fn named(name: &str) -> Self {
+        Self::Named(name.to_owned())
+    }
+
+    /// Types within a category are implicitly cast into each other by Postgres.
+    fn category(&self) -> Option<&str> {
+        match self {
+            Self::Unknown => None,
+            Self::Named(name) => Some(match name.as_str() {
+                "Int2" | "Int4" | "Int8" | "Float4" | "Float8" | "Numeric" => "numeric",
+                "Text" | "Varchar" | "Bpchar" => "string",
+                name => name,
+            }),
+        }
+    }
+
+    /// The array of this type, named like diesel's `Array<Int4>`.
+    pub fn array(&self) -> Self {
+        match self {
+            Self::Unknown => Self::Unknown,
+            Self::Named(name) => Self::Named(format!("Array<{}>", name)),
+        }
+    }
+
+    /// The element type of an array type.
+    pub fn element(&self) -> Option<Self> {
+        match self {
+            Self::Unknown => None,
+            Self::Named(name) => name
+                .strip_prefix("Array<")
+                .and_then(|element| element.strip_suffix('>'))
+                .map(Self::named),
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        matches!(self, Self::Named(name) if name == "Json" || name == "Jsonb")
+    }
+
+    pub fn is_compatible(&self, other: &SqlType) -> bool {
+        match (self.category(), other.category()) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+}
+
+impl std::fmt::Display for SqlType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "unknown"),
+            Self::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnType {
+    pub ty: SqlType,
+    pub nullable: bool,
+}
+
+impl ColumnType {
+    pub fn new(ty: SqlType, nullable: bool) -> Self {
+        Self { ty, nullable }
+    }
+
+    pub fn not_null(name: &str) -> Self {
+        Self::new(SqlType::named(name), false)
+    }
+
+    pub fn with_nullable(&self, nullable: bool) -> Self {
+        Self::new(self.ty.clone(), nullable)
+    }
+}
+
+impl From<&EitherNullableTypeOrIdentifier> for ColumnType {
+    fn from(ty: &EitherNullableTypeOrIdentifier) -> Self {
+        match ty {
+            EitherNullableTypeOrIdentifier::NullableType(ty) => {
+                Self::new(SqlType::Named(ty.get_type_name()), true)
+            }
+            EitherNullableTypeOrIdentifier::Identifier(ty) => {
+                Self::new(SqlType::Named(ty.id.to_string()), false)
+            }
+        }
+    }
+}
+
+// scope
+
+#[derive(Debug, Clone)]
+pub struct ScopeColumn {
+    pub name: String,
+    pub ty: ColumnType,
+}
+
+/// A table (or alias) whose columns can be referenced by expressions.
+#[derive(Debug, Clone)]
+pub struct ScopeTable {
+    pub name: String,
+    pub columns: Vec<ScopeColumn>,
+    /// primary key columns, empty if the table has none
+    pub keys: Vec<String>,
+    /// columns merged into a join column by `USING`, only referenced with the table name
+    pub merged: Vec<String>,
+}
+
+impl ScopeTable {
+    pub fn from_definition(name: String, table: &TableDefinition) -> Self {
+        Self {
+            name,
+            columns: table
+                .def
+                .iter()
+                .map(|field| ScopeColumn {
+                    name: field.name.to_string(),
+                    ty: ColumnType::from(&field.ty),
+                })
+                .collect(),
+            keys: table.keys.iter().map(|key| key.to_string()).collect(),
+            merged: Vec::new(),
+        }
+    }
+
+    /// The columns merged by `USING` or `NATURAL`, which belong to the join instead of a table.
+    pub fn join_columns(columns: Vec<ScopeColumn>) -> Self {
+        Self {
+            name: String::new(),
+            columns,
+            keys: Vec::new(),
+            merged: Vec::new(),
+        }
+    }
+
+    /// The columns of `*` and of references without table name.
+    pub fn unqualified_columns(&self) -> impl Iterator<Item = &ScopeColumn> {
+        self.columns
+            .iter()
+            .filter(move |c| !self.merged.contains(&c.name))
+    }
+
+    /// The name of a column in messages, join columns have no table name.
+    pub fn column_name(&self, column: &str) -> String {
+        if self.name.is_empty() {
+            column.to_owned()
+        } else {
+            format!("{}.{}", self.name, column)
+        }
+    }
+
+    pub fn column(&self, name: &syn::Ident) -> syn::Result<&ScopeColumn> {
+        self.columns
+            .iter()
+            .find(|c| *name == c.name)
+            .ok_or_else(|| {
+                unknown_error(
+                    name,
+                    format!("unknown column `{}` in `{}`", name, self.name),
+                    self.columns.iter().map(|c| c.name.clone()),
+                )
+            })
+    }
+}
+
+/// The tables of a query, subqueries can also reference the tables of their outer queries.
+#[derive(Debug)]
+pub struct Scope<'a> {
+    pub tables: Vec<ScopeTable>,
+    /// tables defined by `WITH`, visible in all nested queries
+    pub ctes: Vec<ScopeTable>,
+    pub schema: &'a Schema,
+    outer: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(schema: &'a Schema) -> Self {
+        Self {
+            tables: Vec::new(),
+            ctes: Vec::new(),
+            schema,
+            outer: None,
+        }
+    }
+
+    /// An empty scope for a subquery.
+    pub fn nested(&self) -> Scope<'_> {
+        Scope {
+            tables: Vec::new(),
+            ctes: Vec::new(),
+            schema: self.schema,
+            outer: Some(self),
+        }
+    }
+
+    pub fn push(&mut self, table: ScopeTable) {
+        self.tables.push(table);
+    }
+
+    /// This scope followed by the outer scopes, innermost first.
+    fn levels(&self) -> Vec<&Scope<'_>> {
+        let mut levels = vec![self];
+        while let Some(outer) = levels[levels.len() - 1].outer {
+            levels.push(outer);
+        }
+
+        levels
+    }
+
+    /// The innermost CTE of that name, ignoring case like [`Scope::table`].
+    pub fn cte(&self, name: &syn::Ident) -> Option<&ScopeTable> {
+        let lowercase = name.to_string().to_lowercase();
+
+        self.levels()
+            .into_iter()
+            .flat_map(|level| level.ctes.iter())
+            .find(|cte| cte.name.to_lowercase() == lowercase)
+    }
+
+    /// Table names are case insensitive, Postgres folds unquoted identifiers to lowercase.
+    pub fn table(&self, name: &syn::Ident) -> syn::Result<&ScopeTable> {
+        let lowercase = name.to_string().to_lowercase();
+        let levels = self.levels();
+        let mut tables = levels.iter().flat_map(|level| level.tables.iter());
+
+        tables
+            .find(|t| t.name.to_lowercase() == lowercase)
+            .ok_or_else(|| {
+                unknown_error(
+                    name,
+                    format!("unknown table `{}`", name),
+                    levels
+                        .iter()
+                        .flat_map(|level| level.tables.iter())
+                        .filter(|t| !t.name.is_empty())
+                        .map(|t| t.name.clone()),
+                )
+            })
+    }
+
+    /// Resolves `column` or `table.column`.
+    pub fn resolve(
+        &self,
+        table: Option<&syn::Ident>,
+        column: &syn::Ident,
+    ) -> syn::Result<&ScopeColumn> {
+        Ok(self.resolve_qualified(table, column)?.1)
+    }
+
+    /// Resolves `column` or `table.column` together with the table it belongs to.
+    pub fn resolve_qualified(
+        &self,
+        table: Option<&syn::Ident>,
+        column: &syn::Ident,
+    ) -> syn::Result<(&ScopeTable, &ScopeColumn)> {
+        if let Some(table) = table {
+            let table = self.table(table)?;
+            return Ok((table, table.column(column)?));
+        }
+
+        // the innermost query with a column of that name wins
+        let name = column.to_string();
+        let levels = self.levels();
+        for level in &levels {
+            let mut found = level.tables.iter().flat_map(|t| {
+                t.unqualified_columns()
+                    .filter(|c| c.name == name)
+                    .map(move |c| (t, c))
+            });
+
+            match (found.next(), found.next()) {
+                (Some(found), None) => return Ok(found),
+                (Some(_), Some(_)) => {
+                    return Err(syn::Error::new(
+                        column.span(),
+                        format!("column reference `{}` is ambiguous", column),
+                    ))
+                }
+                (None, _) => {}
+            }
+        }
+
+        // name the table when there is only one the column could belong to
+        let tables: Vec<_> = levels
+            .iter()
+            .flat_map(|level| level.tables.iter())
+            .collect();
+        let message = match tables.as_slice() {
+            [table] => format!("unknown column `{}` in `{}`", column, table.name),
+            _ => format!("unknown column `{}`", column),
+        };
+
+        Err(unknown_error(
+            column,
+            message,
+            tables
+                .iter()
+                .flat_map(|t| t.columns.iter())
+                .map(|c| c.name.clone()),
+        ))
+    }
+}
+
+// parameters
+
+/// Collects the types inferred for `$n` parameters and the warnings found on the way.
+#[derive(Debug, Default)]
+pub struct Params {
+    params: BTreeMap<usize, (Span, ColumnType)>,
+    warnings: Vec<(Span, String)>,
+}
+
+impl Params {
+    /// Records a warning, they are emitted by the macro once the statement is analyzed.
+    pub fn warn(&mut self, span: Span, message: String) {
+        self.warnings.push((span, message));
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<(Span, String)> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Records a parameter, `ty` is `None` when the context doesn't determine its type.
+    pub fn bind(&mut self, index: &syn::LitInt, ty: Option<&ColumnType>) -> syn::Result<()> {
+        let n: usize = index.base10_parse()?;
+        if n == 0 {
+            return Err(syn::Error::new(
+                index.span(),
+                "parameters are numbered from $1",
+            ));
+        }
+
+        let ty = ty
+            .cloned()
+            .unwrap_or_else(|| ColumnType::new(SqlType::Unknown, false));
+
+        match self.params.get_mut(&n) {
+            None => {
+                self.params.insert(n, (index.span(), ty));
+            }
+            Some(_) if ty.ty == SqlType::Unknown => {}
+            Some((_, existing)) if existing.ty == SqlType::Unknown => {
+                *existing = ty;
+            }
+            Some((_, existing)) => {
+                if ty.ty != existing.ty {
+                    return Err(syn::Error::new(
+                        index.span(),
+                        format!(
+                            "parameter ${} is used as both {} and {}",
+                            n, existing.ty, ty.ty
+                        ),
+                    ));
+                }
+
+                // only nullable if every usage accepts `NULL`
+                existing.nullable &= ty.nullable;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the parameter types ordered by their number, with the span of their first usage.
+    pub fn finish(self) -> syn::Result<Vec<(Span, ColumnType)>> {
+        let mut types = Vec::new();
+
+        for (i, (n, (span, ty))) in self.params.into_iter().enumerate() {
+            if n != i + 1 {
+                return Err(syn::Error::new(
+                    span,
+                    format!("parameter ${} is used, but ${} is not", n, i + 1),
+                ));
+            }
+
+            if ty.ty == SqlType::Unknown {
+                return Err(syn::Error::new(
+                    span,
+                    format!("cannot infer the type of parameter ${}", n),
+                ));
+            }
+
+            types.push((span, ty));
+        }
+
+        Ok(types)
+    }
+}
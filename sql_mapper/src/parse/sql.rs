@@ -1,38 +1,62 @@
 use super::{
-    schema::{EitherNullableTypeOrIdentifier, Schema},
-    try_parse, Identifier, SqlDisplay,
+    builtin,
+    dml::{DeleteStatement, InsertStatement, UpdateStatement},
+    expr::{ColumnRef, Expression, FunctionCall, Literal, WindowSpecification},
+    parse_if_keyword, parse_keyword, parse_parenthesized, peek_keyword,
+    schema::Schema,
+    scope::{ColumnType, Params, Scope, ScopeColumn, ScopeTable, SqlType},
+    sql_string, try_parse, unknown_error, Identifier, SqlDisplay,
 };
 use std::{
     fmt::{Debug, Display},
     marker::PhantomData,
 };
 use syn::{
-    parse::{Parse, ParseStream},
+    parse::{discouraged::Speculative, Parse, ParseStream},
+    token::Paren,
     Token,
 };
 
 // walker
 
 pub trait ProjectionWalker {
-    fn walk_projection(&self, schema: &Schema, f: &mut dyn FnMut(&Projection));
+    fn walk_projection(
+        &self,
+        schema: &Schema,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()>;
+
+    /// The typed output columns, e.g. to define the columns of a derived table.
+    fn projections(&self, schema: &Schema, params: &mut Params) -> syn::Result<Vec<Projection>> {
+        let mut projections = Vec::new();
+        self.walk_projection(schema, params, &mut |projection| {
+            projections.push(projection.clone())
+        })?;
+
+        Ok(projections)
+    }
 }
 
-#[derive(Debug)]
-pub struct Projection<'a> {
-    pub name: String,
-    pub diesel_type: &'a EitherNullableTypeOrIdentifier,
+#[derive(Debug, Clone)]
+pub struct Projection {
+    /// `None` for expressions without alias, which Postgres names `?column?`
+    pub name: Option<String>,
+    pub ty: ColumnType,
+    pub span: proc_macro2::Span,
+    /// The type a json column is deserialized into, with `postgres::types::Json`
+    pub json: Option<syn::Type>,
 }
 
 // parsable structs
 
-///
-///
-///
+/// The macro input: the schema file, the name of the generated struct and the statement.
 #[derive(Debug)]
 pub struct SqlStatement {
     pub file: syn::LitStr,
     pub name: syn::Ident,
-    query: Query,
+    pub flags: Flags,
+    statement: Statement,
 }
 
 impl Parse for SqlStatement {
@@ -43,300 +67,2896 @@ impl Parse for SqlStatement {
         Ok(SqlStatement {
             file: input.parse()?,
             name: input.parse()?,
-            query: input.parse()?,
+            flags: input.parse()?,
+            statement: input.parse()?,
+        })
+    }
+}
+
+/// How many rows the generated `query` returns, `query_one` and `query_opt` are always generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cardinality {
+    Many,
+    One,
+    Optional,
+}
+
+impl SqlStatement {
+    /// All rows, unless `:one`, `:opt` or `:infer` is given.
+    pub fn cardinality(&self, schema: &Schema) -> syn::Result<Cardinality> {
+        let flags: Vec<_> = CARDINALITY_FLAGS
+            .iter()
+            .filter_map(|flag| self.flags.get(flag))
+            .collect();
+
+        let flag = match flags.as_slice() {
+            [] => return Ok(Cardinality::Many),
+            [flag] => flag,
+            [_, flag, ..] => {
+                return Err(syn::Error::new(
+                    flag.span(),
+                    "only one of `:infer`, `:one` and `:opt` can be given",
+                ))
+            }
+        };
+
+        if !self.returns_rows() {
+            return Err(syn::Error::new(
+                flag.span(),
+                format!("`:{}` requires a statement returning rows", flag),
+            ));
+        }
+
+        Ok(match flag.to_string().as_str() {
+            "one" => Cardinality::One,
+            "opt" => Cardinality::Optional,
+            // a primary key lookup returns at most one row
+            _ => match &self.statement {
+                Statement::Query(query) if query.fixes_key(schema) => Cardinality::Optional,
+                _ => Cardinality::Many,
+            },
+        })
+    }
+
+    /// Whether the statement yields rows, otherwise only the affected row count is returned.
+    pub fn returns_rows(&self) -> bool {
+        match &self.statement {
+            Statement::Query(_) => true,
+            Statement::Insert(insert) => insert.returns_rows(),
+            Statement::Update(update) => update.returns_rows(),
+            Statement::Delete(delete) => delete.returns_rows(),
+        }
+    }
+}
+
+impl ProjectionWalker for SqlStatement {
+    fn walk_projection(
+        &self,
+        schema: &Schema,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        match &self.statement {
+            Statement::Query(query) => query.walk_projection(schema, params, f),
+            Statement::Insert(insert) => insert.walk_projection(schema, params, f),
+            Statement::Update(update) => {
+                update.lint(schema, &self.flags, params)?;
+                update.walk_projection(schema, params, f)
+            }
+            Statement::Delete(delete) => {
+                delete.lint(&self.flags)?;
+                delete.walk_projection(schema, params, f)
+            }
+        }
+    }
+}
+
+impl Display for SqlStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.statement {
+            Statement::Query(query) => SqlDisplay::fmt_sql(query.as_ref(), f, 0),
+            Statement::Insert(insert) => SqlDisplay::fmt_sql(insert, f, 0),
+            Statement::Update(update) => SqlDisplay::fmt_sql(update, f, 0),
+            Statement::Delete(delete) => SqlDisplay::fmt_sql(delete, f, 0),
+        }
+    }
+}
+
+/// Opt-in checks given as `:flag` after the statement name.
+#[derive(Debug)]
+pub struct Flags(Vec<syn::Ident>);
+
+const FLAGS: &[&str] = &[
+    // reject `UPDATE ... SET` of primary key columns
+    "deny_key_update",
+    // `query` returns an `Option` for a primary key lookup
+    "infer",
+    // `query` returns exactly one row
+    "one",
+    // `query` returns at most one row
+    "opt",
+    // reject `UPDATE` and `DELETE` without `WHERE`
+    "require_where",
+];
+
+const CARDINALITY_FLAGS: &[&str] = &["infer", "one", "opt"];
+
+impl Parse for Flags {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut flags = Vec::new();
+
+        while input.peek(Token![:]) && !input.peek(Token![::]) {
+            input.parse::<Token![:]>()?;
+
+            let flag: syn::Ident = input.parse()?;
+            if !FLAGS.contains(&flag.to_string().as_str()) {
+                return Err(unknown_error(
+                    &flag,
+                    format!("unknown flag `{}`", flag),
+                    FLAGS.iter().map(|flag| flag.to_string()),
+                ));
+            }
+
+            flags.push(flag);
+        }
+
+        Ok(Self(flags))
+    }
+}
+
+impl Flags {
+    pub fn contains(&self, flag: &str) -> bool {
+        self.get(flag).is_some()
+    }
+
+    fn get(&self, flag: &str) -> Option<&syn::Ident> {
+        self.0.iter().find(|f| *f == flag)
+    }
+}
+
+/// The statements the macro accepts.
+#[derive(Debug)]
+enum Statement {
+    Query(Box<Query>),
+    Insert(InsertStatement),
+    Update(UpdateStatement),
+    Delete(DeleteStatement),
+}
+
+impl Parse for Statement {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        // the kind of statement follows its `WITH` clause
+        let fork = input.fork();
+        parse_if_keyword::<WithClause>(&fork, "WITH")?;
+
+        if peek_keyword(&fork, "INSERT") {
+            return Ok(Self::Insert(input.parse()?));
+        }
+
+        if peek_keyword(&fork, "UPDATE") {
+            return Ok(Self::Update(input.parse()?));
+        }
+
+        if peek_keyword(&fork, "DELETE") {
+            return Ok(Self::Delete(input.parse()?));
+        }
+
+        Ok(Self::Query(Box::new(input.parse()?)))
+    }
+}
+
+/// A query, a select or set operation followed by its sort and row count clauses.
+///
+/// https://www.postgresql.org/docs/current/sql-select.html
+#[derive(Debug)]
+pub struct Query {
+    with_clause: Option<WithClause>,
+    body: QueryBody,
+    order_by_clause: Option<OrderByClause>,
+    limit_clause: Option<LimitClause>,
+    offset_clause: Option<OffsetClause>,
+    fetch_clause: Option<FetchClause>,
+}
+
+impl Parse for Query {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                with_clause: parse_if_keyword(input, "WITH")?,
+                body: QueryBody::parse_set_operations(input, 0)?,
+                order_by_clause: parse_if_keyword(input, "ORDER")?,
+                limit_clause: parse_if_keyword(input, "LIMIT")?,
+                offset_clause: parse_if_keyword(input, "OFFSET")?,
+                fetch_clause: parse_if_keyword(input, "FETCH")?,
+            })
+        })
+    }
+}
+
+impl ProjectionWalker for Query {
+    fn walk_projection(
+        &self,
+        schema: &Schema,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        self.walk(&Scope::new(schema), params, f)
+    }
+}
+
+impl Query {
+    /// Walks the projection, `outer` holds the tables of the outer queries of a subquery.
+    pub fn walk(
+        &self,
+        outer: &Scope,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        let mut with_scope = outer.nested();
+        if let Some(with_clause) = &self.with_clause {
+            with_clause.register(&mut with_scope, params)?;
+        }
+
+        let outer = &with_scope;
+
+        // the sort keys of a set operation can only reference its output columns
+        let (scope, output, count) = match &self.body {
+            QueryBody::Select(select) => {
+                let scope = select.scope(outer, params)?;
+                let count = select.walk(&scope, params, f)?;
+                (
+                    scope,
+                    select.select_clause.star_or_sublists.output_names(),
+                    count,
+                )
+            }
+            body => {
+                let projections = body.projections(outer, params)?;
+                projections.iter().for_each(f);
+                let output = projections.iter().filter_map(|p| p.name.clone()).collect();
+                (outer.nested(), output, projections.len())
+            }
+        };
+
+        if let Some(order_by_clause) = &self.order_by_clause {
+            for sort_key in &order_by_clause.sort_keys.0 {
+                if let QueryBody::Select(select) = &self.body {
+                    select.check_window_references(&sort_key.expression)?;
+                }
+
+                sort_key.infer(&scope, params, &output, count)?;
+            }
+
+            if let QueryBody::Select(select) = &self.body {
+                select.check_distinct_on(&order_by_clause.sort_keys.0, &scope)?;
+            }
+        }
+
+        if let Some(limit_clause) = &self.limit_clause {
+            if let EitherAllOrExpression::Expression(count) = &limit_clause.count {
+                infer_row_count(count, outer, params)?;
+            }
+        }
+
+        if let Some(offset_clause) = &self.offset_clause {
+            infer_row_count(&offset_clause.start, outer, params)?;
+        }
+
+        if let Some(FetchClause {
+            count: Some(count), ..
+        }) = &self.fetch_clause
+        {
+            infer_row_count(count, outer, params)?;
+        }
+
+        Ok(())
+    }
+
+    /// The typed output columns.
+    pub fn columns(&self, outer: &Scope, params: &mut Params) -> syn::Result<Vec<Projection>> {
+        let mut columns = Vec::new();
+        self.walk(outer, params, &mut |projection| {
+            columns.push(projection.clone())
+        })?;
+
+        Ok(columns)
+    }
+
+    /// Whether `WHERE` fixes each primary key column of the only table, which returns at most
+    /// one row then.
+    fn fixes_key(&self, schema: &Schema) -> bool {
+        let select = match &self.body {
+            QueryBody::Select(select) if self.with_clause.is_none() => select,
+            _ => return false,
+        };
+
+        let (from_clause, where_clause) = match &select.table_expression {
+            Some(TableExpression {
+                from_clause,
+                where_clause: Some(where_clause),
+                ..
+            }) => (from_clause, where_clause),
+            _ => return false,
+        };
+
+        let table = match from_clause.table_references.0.iter().collect::<Vec<_>>()[..] {
+            [TableReference {
+                joined_table:
+                    JoinedTable {
+                        table_primary: TablePrimary::TableName(table),
+                        joins,
+                    },
+            }] if joins.is_empty() => table,
+            _ => return false,
+        };
+
+        let definition = match schema.table(&table.identifier.id) {
+            Ok(definition) => definition,
+            Err(_) => return false,
+        };
+
+        let name = match &table.as_ {
+            Some((_, alias)) => &alias.id,
+            None => &table.identifier.id,
+        };
+
+        let fixed: Vec<_> = where_clause
+            .condition
+            .conjuncts()
+            .into_iter()
+            .filter_map(Expression::fixed_column)
+            .filter(|column| match &column.table {
+                Some((table, _)) => table.id == *name,
+                None => true,
+            })
+            .collect();
+
+        definition
+            .keys
+            .iter()
+            .all(|key| fixed.iter().any(|column| column.column.id == *key))
+    }
+
+    /// The name Postgres gives a scalar subquery, the name of its only column.
+    pub fn derived_name(&self) -> Option<String> {
+        self.only_sublist()?.1.name()
+    }
+
+    /// Infers the only output expression again with the type the context expects, so
+    /// `x IN (SELECT unnest($1))` types the parameter from `x`.
+    pub fn infer_column_as(
+        &self,
+        outer: &Scope,
+        params: &mut Params,
+        expected: &ColumnType,
+    ) -> syn::Result<Option<ColumnType>> {
+        let (select, sublist) = match self.only_sublist() {
+            Some(only) => only,
+            None => return Ok(None),
+        };
+
+        let mut with_scope = outer.nested();
+        if let Some(with_clause) = &self.with_clause {
+            with_clause.register(&mut with_scope, params)?;
+        }
+
+        let scope = select.scope(&with_scope, params)?;
+        Ok(Some(sublist.expression.infer(
+            &scope,
+            params,
+            Some(expected),
+        )?))
+    }
+
+    /// The `SELECT` with a single expression in its select list.
+    fn only_sublist(&self) -> Option<(&SimpleSelect, &SelectSublist)> {
+        let select = match &self.body {
+            QueryBody::Select(select) => select.as_ref(),
+            _ => return None,
+        };
+
+        match &select.select_clause.star_or_sublists {
+            EitherStarOrPunctSelectSublist::PunctSelectSublist(sublists) => {
+                match sublists.0.first() {
+                    Some(EitherQualifiedStarOrSelectSublist::SelectSublist(sublist))
+                        if sublists.0.len() == 1 =>
+                    {
+                        Some((select, sublist))
+                    }
+                    _ => None,
+                }
+            }
+            EitherStarOrPunctSelectSublist::Star(_) => None,
+        }
+    }
+}
+
+/// `LIMIT`, `OFFSET` and `FETCH` counts are `bigint` and cannot reference the query's columns.
+fn infer_row_count(count: &Expression, outer: &Scope, params: &mut Params) -> syn::Result<()> {
+    let ty = count.infer(outer, params, Some(&ColumnType::not_null("Int8")))?;
+    if !ty.ty.is_compatible(&SqlType::named("Int8")) || ty.nullable {
+        return Err(syn::Error::new(
+            count.span(),
+            format!("expected a row count, found {}", ty.ty),
+        ));
+    }
+
+    Ok(())
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        SqlDisplay::fmt_sql(self, f, 0)
+    }
+}
+
+impl SqlDisplay for Query {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        if let Some(with_clause) = &self.with_clause {
+            indent(f, level)?;
+            with_clause.fmt_sql(f, level)?;
+            writeln!(f)?;
+        }
+
+        self.body.fmt_sql(f, level)?;
+
+        if let Some(order_by_clause) = &self.order_by_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            order_by_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(limit_clause) = &self.limit_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            limit_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(offset_clause) = &self.offset_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            offset_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(fetch_clause) = &self.fetch_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            fetch_clause.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `WITH` and the common table expressions it defines for the statement.
+///
+/// https://www.postgresql.org/docs/current/queries-with.html
+#[derive(Debug)]
+pub struct WithClause {
+    with: With,
+    recursive: Option<Recursive>,
+    ctes: Punctuated<CommonTableExpression, Comma, OneOreMany>,
+}
+
+impl Parse for WithClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            with: input.parse()?,
+            recursive: input.parse().ok(),
+            ctes: input.parse()?,
+        })
+    }
+}
+
+impl WithClause {
+    /// Defines the CTEs in `scope`, each CTE can reference the ones before it.
+    pub fn register(&self, scope: &mut Scope, params: &mut Params) -> syn::Result<()> {
+        for cte in &self.ctes.0 {
+            let lowercase = cte.name.id.to_string().to_lowercase();
+            if scope
+                .ctes
+                .iter()
+                .any(|c| c.name.to_lowercase() == lowercase)
+            {
+                return Err(syn::Error::new(
+                    cte.name.id.span(),
+                    format!("WITH query name `{}` specified more than once", cte.name.id),
+                ));
+            }
+
+            let table = if self.recursive.is_some() {
+                cte.recursive_table(scope, params)?
+            } else {
+                cte.table(scope, params)?
+            };
+
+            scope.ctes.push(table);
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for WithClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.with.fmt_sql(f, level)?;
+
+        if let Some(recursive) = &self.recursive {
+            write!(f, " ")?;
+            recursive.fmt_sql(f, level)?;
+        }
+
+        for (i, cte) in self.ctes.0.iter().enumerate() {
+            write!(f, "{}", if i == 0 { " " } else { ", " })?;
+            cte.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A named query of a `WITH` clause, usable like a table in the statement.
+#[derive(Debug)]
+struct CommonTableExpression {
+    name: Identifier,
+    column_aliases: Option<(Paren, PunctIdentifier)>,
+    as_: As,
+    paren: Paren,
+    query: Box<Query>,
+}
+
+impl Parse for CommonTableExpression {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let column_aliases = if input.peek(Paren) {
+            Some(parse_parenthesized(input)?)
+        } else {
+            None
+        };
+        let as_ = input.parse()?;
+        let (paren, query) = parse_parenthesized(input)?;
+
+        Ok(Self {
+            name,
+            column_aliases,
+            as_,
+            paren,
+            query: Box::new(query),
         })
     }
 }
 
-impl ProjectionWalker for SqlStatement {
-    fn walk_projection(&self, schema: &Schema, f: &mut dyn FnMut(&Projection)) {
-        self.query.walk_projection(schema, f)
+impl CommonTableExpression {
+    fn table(&self, scope: &Scope, params: &mut Params) -> syn::Result<ScopeTable> {
+        let projections = self.query.columns(scope, params)?;
+        query_table(&self.name, projections, &self.column_aliases)
+    }
+
+    /// The non-recursive term of `non_recursive UNION recursive` defines the column types,
+    /// the recursive term, which references the CTE itself, may only add nullability.
+    fn recursive_table(&self, scope: &mut Scope, params: &mut Params) -> syn::Result<ScopeTable> {
+        let initial = match &self.query.body {
+            QueryBody::SetOperation(non_recursive, _, _) => {
+                non_recursive.projections(scope, params)?
+            }
+            _ => return self.table(scope, params),
+        };
+
+        let mut table = query_table(&self.name, initial, &self.column_aliases)?;
+
+        // nullability only grows, so this ends
+        loop {
+            scope.ctes.push(table);
+            let projections = self.query.columns(scope, params);
+            let previous = scope.ctes.pop().expect("CTE pushed before");
+
+            let next = query_table(&self.name, projections?, &self.column_aliases)?;
+            for (i, (previous, next)) in previous.columns.iter().zip(&next.columns).enumerate() {
+                if previous.ty.ty != next.ty.ty {
+                    return Err(syn::Error::new(
+                        self.paren.span,
+                        format!(
+                            "recursive query `{}` column {} has type {} in non-recursive term but type {} overall",
+                            self.name.id,
+                            i + 1,
+                            previous.ty.ty,
+                            next.ty.ty
+                        ),
+                    ));
+                }
+            }
+
+            if previous
+                .columns
+                .iter()
+                .zip(&next.columns)
+                .all(|(previous, next)| previous.ty == next.ty)
+            {
+                return Ok(next);
+            }
+
+            table = next;
+        }
+    }
+}
+
+impl SqlDisplay for CommonTableExpression {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.name.fmt_sql(f, level)?;
+        fmt_column_aliases(&self.column_aliases, f, level)?;
+        write!(f, " ")?;
+        self.as_.fmt_sql(f, level)?;
+        writeln!(f, " (")?;
+        self.query.fmt_sql(f, level + 1)?;
+        writeln!(f)?;
+        indent(f, level)?;
+        write!(f, ")")
+    }
+}
+
+/// The rows of a query, a single select or a set operation combining two queries.
+///
+/// https://www.postgresql.org/docs/current/queries-union.html
+#[derive(Debug)]
+enum QueryBody {
+    Select(Box<SimpleSelect>),
+    Nested(Box<Query>),
+    SetOperation(Box<QueryBody>, SetOperator, Box<QueryBody>),
+}
+
+impl QueryBody {
+    /// Parses set operations, where `INTERSECT` binds tighter than `UNION` and `EXCEPT`.
+    fn parse_set_operations(input: syn::parse::ParseStream, precedence: u8) -> syn::Result<Self> {
+        let mut body = if input.peek(Paren) {
+            let (_, query) = parse_parenthesized(input)?;
+            Self::Nested(Box::new(query))
+        } else {
+            Self::Select(Box::new(input.parse()?))
+        };
+
+        loop {
+            let fork = input.fork();
+            match fork.parse::<SetOperator>() {
+                Ok(op) if op.precedence() >= precedence => {
+                    input.advance_to(&fork);
+                    let right = Self::parse_set_operations(input, op.precedence() + 1)?;
+                    body = Self::SetOperation(Box::new(body), op, Box::new(right));
+                }
+                _ => return Ok(body),
+            }
+        }
+    }
+
+    /// The output columns, for set operations merged from both sides.
+    fn projections(&self, outer: &Scope, params: &mut Params) -> syn::Result<Vec<Projection>> {
+        let mut projections = Vec::new();
+
+        match self {
+            Self::Select(select) => {
+                let scope = select.scope(outer, params)?;
+                select.walk(&scope, params, &mut |p| projections.push(p.clone()))?;
+            }
+            Self::Nested(query) => {
+                projections = query.columns(outer, params)?;
+            }
+            Self::SetOperation(left, op, right) => {
+                let left = left.projections(outer, params)?;
+                let right = right.projections(outer, params)?;
+
+                if left.len() != right.len() {
+                    return Err(syn::Error::new(
+                        op.span,
+                        format!("each {} query must have the same number of columns", op),
+                    ));
+                }
+
+                for (left, right) in left.into_iter().zip(right) {
+                    projections.push(op.merge(left, right)?);
+                }
+            }
+        }
+
+        Ok(projections)
+    }
+}
+
+impl SqlDisplay for QueryBody {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::Select(select) => select.fmt_sql(f, level),
+            Self::Nested(query) => {
+                indent(f, level)?;
+                writeln!(f, "(")?;
+                query.fmt_sql(f, level + 1)?;
+                writeln!(f)?;
+                indent(f, level)?;
+                write!(f, ")")
+            }
+            Self::SetOperation(left, op, right) => {
+                left.fmt_sql(f, level)?;
+                writeln!(f)?;
+                indent(f, level)?;
+                op.fmt_sql(f, level)?;
+                writeln!(f)?;
+                right.fmt_sql(f, level)
+            }
+        }
+    }
+}
+
+/// A `UNION`, `INTERSECT` or `EXCEPT` between two queries.
+#[derive(Debug)]
+struct SetOperator {
+    kind: SetOperatorKind,
+    span: proc_macro2::Span,
+    all_or_distinct: Option<EitherAllOrDistinct>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SetOperatorKind {
+    Union,
+    Intersect,
+    Except,
+}
+
+const SET_OPERATORS: &[(&str, SetOperatorKind)] = &[
+    ("UNION", SetOperatorKind::Union),
+    ("INTERSECT", SetOperatorKind::Intersect),
+    ("EXCEPT", SetOperatorKind::Except),
+];
+
+impl Parse for SetOperator {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        for (keyword, kind) in SET_OPERATORS {
+            if let Ok(span) = parse_keyword(input, keyword) {
+                return Ok(Self {
+                    kind: *kind,
+                    span,
+                    all_or_distinct: input.parse().ok(),
+                });
+            }
+        }
+
+        Err(input.error("expected set operation"))
+    }
+}
+
+impl SetOperator {
+    fn precedence(&self) -> u8 {
+        match self.kind {
+            SetOperatorKind::Union | SetOperatorKind::Except => 1,
+            SetOperatorKind::Intersect => 2,
+        }
+    }
+
+    /// Merges an output column of both sides, the name is taken from the left side.
+    fn merge(&self, left: Projection, right: Projection) -> syn::Result<Projection> {
+        let ty = match (&left.ty.ty, &right.ty.ty) {
+            (SqlType::Unknown, ty) | (ty, SqlType::Unknown) => ty.clone(),
+            (SqlType::Named(l), SqlType::Named(r)) => match builtin::common_type(l, r) {
+                Some(ty) => SqlType::named(ty),
+                None => {
+                    return Err(syn::Error::new(
+                        self.span,
+                        format!("{} types {} and {} cannot be matched", self, l, r),
+                    ))
+                }
+            },
+        };
+
+        let nullable = match self.kind {
+            SetOperatorKind::Union => left.ty.nullable || right.ty.nullable,
+            // rows are in both results
+            SetOperatorKind::Intersect => left.ty.nullable && right.ty.nullable,
+            // rows are from the left result
+            SetOperatorKind::Except => left.ty.nullable,
+        };
+
+        Ok(Projection {
+            ty: ColumnType::new(ty, nullable),
+            ..left
+        })
+    }
+}
+
+impl Display for SetOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            SetOperatorKind::Union => write!(f, "UNION"),
+            SetOperatorKind::Intersect => write!(f, "INTERSECT"),
+            SetOperatorKind::Except => write!(f, "EXCEPT"),
+        }
+    }
+}
+
+impl SqlDisplay for SetOperator {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        write!(f, "{}", self)?;
+
+        if let Some(all_or_distinct) = &self.all_or_distinct {
+            write!(f, " ")?;
+            all_or_distinct.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single `SELECT` with its table expression.
+#[derive(Debug)]
+struct SimpleSelect {
+    select_clause: SelectClause,
+    table_expression: Option<TableExpression>,
+}
+
+impl Parse for SimpleSelect {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            select_clause: input.parse()?,
+            table_expression: parse_if_keyword(input, "FROM")?,
+        })
+    }
+}
+
+impl SimpleSelect {
+    fn scope<'a>(&self, outer: &'a Scope, params: &mut Params) -> syn::Result<Scope<'a>> {
+        let mut scope = outer.nested();
+
+        if let Some(table_expression) = &self.table_expression {
+            for tr in &table_expression.from_clause.table_references.0 {
+                let tables = tr.joined_table.scope_tables(outer, &scope.tables, params)?;
+                for table in tables {
+                    scope.push(table);
+                }
+            }
+        }
+
+        Ok(scope)
+    }
+
+    /// Checks the clauses and walks the projection, returns the number of output columns.
+    fn walk(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<usize> {
+        if let EitherStarOrPunctSelectSublist::Star(star) = &self.select_clause.star_or_sublists {
+            if self.table_expression.is_none() {
+                return Err(syn::Error::new(star.span, "`*` requires a FROM clause"));
+            }
+        }
+
+        let table_expression = self.table_expression.as_ref();
+        if let Some(where_clause) = table_expression.and_then(|t| t.where_clause.as_ref()) {
+            let condition = &where_clause.condition;
+            if condition.contains_aggregate() {
+                return Err(syn::Error::new(
+                    condition.span(),
+                    "aggregate functions are not allowed in WHERE",
+                ));
+            }
+
+            deny_window_functions(condition, "WHERE")?;
+            condition.infer_condition(scope, params)?;
+        }
+
+        let star_or_sublists = &self.select_clause.star_or_sublists;
+        let group_by_clause = table_expression.and_then(|t| t.group_by_clause.as_ref());
+        let having_clause = table_expression.and_then(|t| t.having_clause.as_ref());
+        let window_clause = table_expression.and_then(|t| t.window_clause.as_ref());
+
+        if let Some(window_clause) = window_clause {
+            window_clause.infer(scope, params)?;
+        }
+
+        for sublist in star_or_sublists.sublists() {
+            self.check_window_references(&sublist.expression)?;
+        }
+
+        let mut count = 0;
+        star_or_sublists.walk(scope, params, &mut |projection| {
+            count += 1;
+            f(projection)
+        })?;
+
+        let output = star_or_sublists.output_names();
+        if let Some(distinct_on) = self.select_clause.distinct_on() {
+            for expression in &distinct_on.expressions.0 {
+                self.check_window_references(expression)?;
+                infer_output_expression(expression, scope, params, &output, count, "DISTINCT ON")?;
+            }
+        }
+
+        if let Some(having_clause) = having_clause {
+            deny_window_functions(&having_clause.condition, "HAVING")?;
+            having_clause.condition.infer_condition(scope, params)?;
+        }
+
+        if group_by_clause.is_some() || having_clause.is_some() || star_or_sublists.has_aggregate()
+        {
+            let grouping = Grouping::new(group_by_clause, star_or_sublists, scope, params)?;
+            star_or_sublists.check_grouped(&grouping, scope)?;
+
+            if let Some(having_clause) = having_clause {
+                grouping.check(&having_clause.condition, scope)?;
+            }
+
+            if let Some(distinct_on) = self.select_clause.distinct_on() {
+                for expression in &distinct_on.expressions.0 {
+                    grouping.check(self.output_expression(expression, scope)?, scope)?;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// The selected expression an `ORDER BY` or `DISTINCT ON` item refers to by position or name.
+    fn output_expression<'a>(
+        &'a self,
+        expression: &'a Expression,
+        scope: &'a Scope,
+    ) -> syn::Result<&'a Expression> {
+        let star_or_sublists = &self.select_clause.star_or_sublists;
+
+        let sublist = match expression {
+            Expression::Literal(Literal::Integer(position)) => {
+                let outputs = star_or_sublists.outputs(scope)?;
+                match position
+                    .base10_parse::<usize>()
+                    .ok()
+                    .and_then(|n| outputs.into_iter().nth(n.checked_sub(1)?))
+                {
+                    Some(Output::Sublist(sublist)) => Some(sublist),
+                    _ => None,
+                }
+            }
+            Expression::Column(ColumnRef {
+                table: None,
+                column,
+            }) => star_or_sublists
+                .sublists()
+                .into_iter()
+                .find(|sublist| sublist.name() == Some(column.id.to_string())),
+            _ => None,
+        };
+
+        Ok(sublist.map_or(expression, |sublist| &sublist.expression))
+    }
+
+    /// What an `ORDER BY` or `DISTINCT ON` item sorts by, columns are identified by their table.
+    fn sort_identity(&self, expression: &Expression, scope: &Scope) -> syn::Result<String> {
+        if let Expression::Literal(Literal::Integer(position)) = expression {
+            let outputs = self.select_clause.star_or_sublists.outputs(scope)?;
+            let n: usize = position.base10_parse()?;
+
+            if let Some(Output::Column(table, column, _)) =
+                n.checked_sub(1).and_then(|i| outputs.into_iter().nth(i))
+            {
+                return Ok(format!("{}.{}", table.name, column.name));
+            }
+        }
+
+        match self.output_expression(expression, scope)? {
+            Expression::Column(column) => {
+                let (table, column) = column.qualified(scope)?;
+                Ok(format!("{}.{}", table, column))
+            }
+            expression => Ok(sql_string(expression)),
+        }
+    }
+
+    /// `DISTINCT ON` keeps the first row of each group, so the sort has to start with its expressions.
+    fn check_distinct_on<'a>(
+        &self,
+        sort_keys: impl IntoIterator<Item = &'a SortKey>,
+        scope: &Scope,
+    ) -> syn::Result<()> {
+        let distinct_on = match self.select_clause.distinct_on() {
+            Some(distinct_on) => distinct_on,
+            None => return Ok(()),
+        };
+
+        let mut expressions = Vec::new();
+        for expression in &distinct_on.expressions.0 {
+            expressions.push(self.sort_identity(expression, scope)?);
+        }
+
+        let mut unsorted = expressions.clone();
+
+        for sort_key in sort_keys {
+            if unsorted.is_empty() {
+                break;
+            }
+
+            let key = self.sort_identity(&sort_key.expression, scope)?;
+            if !expressions.contains(&key) {
+                return Err(syn::Error::new(
+                    sort_key.expression.span(),
+                    "SELECT DISTINCT ON expressions must match initial ORDER BY expressions",
+                ));
+            }
+
+            unsorted.retain(|e| *e != key);
+        }
+
+        Ok(())
+    }
+
+    /// Windows referenced by name have to be defined by the `WINDOW` clause.
+    fn check_window_references(&self, expression: &Expression) -> syn::Result<()> {
+        let window_clause = self
+            .table_expression
+            .as_ref()
+            .and_then(|t| t.window_clause.as_ref());
+
+        for reference in expression.window_references() {
+            if !window_clause.is_some_and(|w| w.defines(reference)) {
+                return Err(syn::Error::new(
+                    reference.id.span(),
+                    format!("window `{}` does not exist", reference.id),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Window functions are evaluated after `WHERE`, `GROUP BY` and `HAVING`.
+fn deny_window_functions(expression: &Expression, clause: &str) -> syn::Result<()> {
+    if expression.contains_window() {
+        return Err(syn::Error::new(
+            expression.span(),
+            format!("window functions are not allowed in {}", clause),
+        ));
+    }
+
+    Ok(())
+}
+
+impl SqlDisplay for SimpleSelect {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        indent(f, level)?;
+        self.select_clause.fmt_sql(f, level)?;
+
+        if let Some(table_expression) = &self.table_expression {
+            table_expression.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The clauses after the select list, which the output columns are computed from.
+///
+/// https://www.postgresql.org/docs/current/queries-table-expressions.html
+#[derive(Debug)]
+struct TableExpression {
+    from_clause: FromClause,
+    where_clause: Option<WhereClause>,
+    group_by_clause: Option<GroupByClause>,
+    having_clause: Option<HavingClause>,
+    window_clause: Option<WindowClause>,
+}
+
+impl Parse for TableExpression {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                from_clause: input.parse()?,
+                where_clause: parse_if_keyword(input, "WHERE")?,
+                group_by_clause: parse_if_keyword(input, "GROUP")?,
+                having_clause: parse_if_keyword(input, "HAVING")?,
+                window_clause: parse_if_keyword(input, "WINDOW")?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for TableExpression {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        writeln!(f)?;
+        indent(f, level)?;
+        self.from_clause.fmt_sql(f, level)?;
+
+        if let Some(where_clause) = &self.where_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            where_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(group_by_clause) = &self.group_by_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            group_by_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(having_clause) = &self.having_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            having_clause.fmt_sql(f, level)?;
+        }
+
+        if let Some(window_clause) = &self.window_clause {
+            writeln!(f)?;
+            indent(f, level)?;
+            window_clause.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `SELECT` with its select list.
+#[derive(Debug)]
+struct SelectClause {
+    select: Select,
+    set_quantifier: Option<SetQuantifier>,
+    star_or_sublists: EitherStarOrPunctSelectSublist,
+}
+
+impl Parse for SelectClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                select: input.parse()?,
+                set_quantifier: if peek_keyword(input, "ALL") || peek_keyword(input, "DISTINCT") {
+                    Some(input.parse()?)
+                } else {
+                    None
+                },
+                star_or_sublists: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SelectClause {
+    fn distinct_on(&self) -> Option<&DistinctOn> {
+        match &self.set_quantifier {
+            Some(SetQuantifier::Distinct(_, distinct_on)) => distinct_on.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl SqlDisplay for SelectClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.select.fmt_sql(f, level)?;
+
+        if let Some(v) = &self.set_quantifier {
+            write!(f, " ")?;
+            v.fmt_sql(f, level)?;
+        }
+
+        {
+            let level = level + 1;
+            writeln!(f)?;
+            indent(f, level)?;
+            self.star_or_sublists.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `ALL` or `DISTINCT` rows of a select, optionally distinct on some expressions.
+///
+/// https://www.postgresql.org/docs/current/sql-select.html#SQL-DISTINCT
+#[derive(Debug)]
+enum SetQuantifier {
+    All(All),
+    Distinct(Distinct, Option<DistinctOn>),
+}
+
+impl Parse for SetQuantifier {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if peek_keyword(input, "ALL") {
+            return Ok(Self::All(input.parse()?));
+        }
+
+        Ok(Self::Distinct(
+            input.parse()?,
+            parse_if_keyword(input, "ON")?,
+        ))
+    }
+}
+
+impl SqlDisplay for SetQuantifier {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::All(all) => all.fmt_sql(f, level),
+            Self::Distinct(distinct, distinct_on) => {
+                distinct.fmt_sql(f, level)?;
+
+                if let Some(distinct_on) = distinct_on {
+                    write!(f, " ")?;
+                    distinct_on.fmt_sql(f, level)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Keeps the first row of each group of rows where the expressions are equal.
+#[derive(Debug)]
+struct DistinctOn {
+    on: On,
+    expressions: Punctuated<Expression, Comma, OneOreMany>,
+}
+
+impl Parse for DistinctOn {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let on = input.parse()?;
+        let (_, expressions) = parse_parenthesized(input)?;
+
+        Ok(Self { on, expressions })
+    }
+}
+
+impl SqlDisplay for DistinctOn {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.on.fmt_sql(f, level)?;
+        write!(f, " (")?;
+        self.expressions.fmt_sql(f, level)?;
+        write!(f, ")")
+    }
+}
+
+impl EitherStarOrPunctSelectSublist {
+    pub fn walk(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        for output in self.outputs(scope)? {
+            match output {
+                Output::Column(table, column, span) => f(&Projection {
+                    name: Some(table.column_name(&column.name)),
+                    ty: column.ty.clone(),
+                    span,
+                    json: None,
+                }),
+                Output::Sublist(sublist) => sublist.walk(scope, params, f)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The output columns, with `*` and `table.*` expanded to the columns of their tables.
+    fn outputs<'a>(&'a self, scope: &'a Scope) -> syn::Result<Vec<Output<'a>>> {
+        let mut outputs = Vec::new();
+
+        match self {
+            Self::Star(_) => {
+                for table in &scope.tables {
+                    for column in table.unqualified_columns() {
+                        outputs.push(Output::Column(
+                            table,
+                            column,
+                            proc_macro2::Span::call_site(),
+                        ));
+                    }
+                }
+            }
+            Self::PunctSelectSublist(sublists) => {
+                for item in &sublists.0 {
+                    match item {
+                        EitherQualifiedStarOrSelectSublist::QualifiedStar(star) => {
+                            let table = scope.table(&star.table.id)?;
+                            for column in &table.columns {
+                                outputs.push(Output::Column(table, column, star.table.id.span()));
+                            }
+                        }
+                        EitherQualifiedStarOrSelectSublist::SelectSublist(sublist) => {
+                            outputs.push(Output::Sublist(sublist))
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    /// The selected expressions, without stars.
+    fn sublists(&self) -> Vec<&SelectSublist> {
+        match self {
+            Self::Star(_) => Vec::new(),
+            Self::PunctSelectSublist(sublists) => sublists
+                .0
+                .iter()
+                .filter_map(|item| match item {
+                    EitherQualifiedStarOrSelectSublist::SelectSublist(sublist) => Some(sublist),
+                    EitherQualifiedStarOrSelectSublist::QualifiedStar(_) => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn has_aggregate(&self) -> bool {
+        self.sublists()
+            .iter()
+            .any(|sublist| sublist.expression.contains_aggregate())
+    }
+
+    fn check_grouped(&self, grouping: &Grouping, scope: &Scope) -> syn::Result<()> {
+        for output in self.outputs(scope)? {
+            match output {
+                Output::Column(table, column, span) => {
+                    if !grouping.contains_column(table, &column.name) {
+                        return Err(syn::Error::new(
+                            span,
+                            not_grouped_message(table, &column.name),
+                        ));
+                    }
+                }
+                Output::Sublist(sublist) => grouping.check(&sublist.expression, scope)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Names of the explicitly selected output columns.
+    fn output_names(&self) -> Vec<String> {
+        self.sublists()
+            .into_iter()
+            .filter_map(SelectSublist::name)
+            .collect()
+    }
+}
+
+/// An output column of the select list.
+enum Output<'a> {
+    /// a column of `*` or `table.*`
+    Column(&'a ScopeTable, &'a ScopeColumn, proc_macro2::Span),
+    Sublist(&'a SelectSublist),
+}
+
+/// `table.*`, all columns of a table in schema order.
+#[derive(Debug)]
+pub struct QualifiedStar {
+    table: Identifier,
+    star: Star,
+}
+
+impl Parse for QualifiedStar {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            let table = input.parse()?;
+            input.parse::<Token![.]>()?;
+
+            Ok(Self {
+                table,
+                star: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for QualifiedStar {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.table.fmt_sql(f, level)?;
+        write!(f, ".")?;
+        self.star.fmt_sql(f, level)
+    }
+}
+
+/// An output column of the select list.
+#[derive(Debug)]
+pub struct SelectSublist {
+    expression: Expression,
+    alias: Option<(Option<As>, ColumnAlias)>,
+}
+
+impl Parse for SelectSublist {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                expression: input.parse()?,
+                alias: try_parse(input, |input| Ok((input.parse().ok(), input.parse()?))).ok(),
+            })
+        })
+    }
+}
+
+impl SelectSublist {
+    fn walk(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        f: &mut dyn FnMut(&Projection),
+    ) -> syn::Result<()> {
+        let mut ty = self.expression.infer(scope, params, None)?;
+        let mut json = None;
+        if let Some((_, alias)) = &self.alias {
+            if let Some(nullable) = alias.nullable_override()? {
+                ty = ty.with_nullable(nullable);
+            }
+
+            json = alias.json_type()?;
+            if json.is_some() && !ty.ty.is_json() {
+                return Err(syn::Error::new(
+                    self.expression.span(),
+                    format!(
+                        "only json columns can be deserialized into a type, found {}",
+                        ty.ty
+                    ),
+                ));
+            }
+        }
+
+        f(&Projection {
+            name: self.name(),
+            ty,
+            span: self.expression.span(),
+            json,
+        });
+
+        Ok(())
+    }
+
+    fn name(&self) -> Option<String> {
+        match &self.alias {
+            Some((_, alias)) => Some(alias.name()),
+            None => self.expression.derived_name(),
+        }
+    }
+}
+
+impl SqlDisplay for SelectSublist {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.expression.fmt_sql(f, level)?;
+
+        if let Some((as_, alias)) = &self.alias {
+            if let Some(as_) = as_ {
+                write!(f, " ")?;
+                as_.fmt_sql(f, level)?;
+            }
+
+            write!(f, " ")?;
+            alias.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The name of an output column, quoted names can override the nullability.
+///
+/// https://www.postgresql.org/docs/current/sql-syntax-lexical.html#SQL-SYNTAX-IDENTIFIERS
+#[derive(Debug)]
+enum ColumnAlias {
+    Identifier(Identifier),
+    /// `"name"`, where a trailing `!` forces a field to be non-null and `?` to be nullable,
+    /// `"name: Type"` deserializes a json column into `Type`
+    Quoted(syn::LitStr),
+}
+
+impl Parse for ColumnAlias {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::LitStr) {
+            Ok(Self::Quoted(input.parse()?))
+        } else {
+            Ok(Self::Identifier(input.parse()?))
+        }
+    }
+}
+
+impl ColumnAlias {
+    /// The name without a nullability override.
+    fn name(&self) -> String {
+        match self {
+            Self::Identifier(identifier) => identifier.id.to_string(),
+            Self::Quoted(_) => {
+                let value = self.quoted_name();
+                match value.strip_suffix(&['!', '?'][..]) {
+                    Some(name) => name.to_owned(),
+                    None => value,
+                }
+            }
+        }
+    }
+
+    /// The quoted name with a nullability override, but without the type.
+    fn quoted_name(&self) -> String {
+        match self {
+            Self::Identifier(identifier) => identifier.id.to_string(),
+            Self::Quoted(lit) => match lit.value().split_once(':') {
+                Some((name, _)) => name.trim_end().to_owned(),
+                None => lit.value(),
+            },
+        }
+    }
+
+    fn json_type(&self) -> syn::Result<Option<syn::Type>> {
+        let (lit, ty) = match self {
+            Self::Quoted(lit) => match lit.value().split_once(':') {
+                Some((_, ty)) => (lit, ty.trim().to_owned()),
+                None => return Ok(None),
+            },
+            Self::Identifier(_) => return Ok(None),
+        };
+
+        syn::parse_str(&ty)
+            .map(Some)
+            .map_err(|_| syn::Error::new(lit.span(), format!("`{}` is no valid type", ty)))
+    }
+
+    fn nullable_override(&self) -> syn::Result<Option<bool>> {
+        let lit = match self {
+            Self::Identifier(_) => return Ok(None),
+            Self::Quoted(lit) => lit,
+        };
+
+        if syn::parse_str::<syn::Ident>(&self.name()).is_err() {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!("`{}` is no valid field name", self.name()),
+            ));
+        }
+
+        Ok(match self.quoted_name().chars().last() {
+            Some('!') => Some(false),
+            Some('?') => Some(true),
+            _ => None,
+        })
+    }
+}
+
+impl SqlDisplay for ColumnAlias {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::Identifier(identifier) => identifier.fmt_sql(f, level),
+            // the override is only for the Rust side, Postgres gets the bare name
+            Self::Quoted(_) => write!(f, "\"{}\"", self.name().replace('"', "\"\"")),
+        }
+    }
+}
+
+///
+///
+/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#from
+#[derive(Debug)]
+struct FromClause {
+    from: From,
+    table_references: Punctuated<TableReference, Comma, OneOreMany>,
+}
+
+impl Parse for FromClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                from: input.parse()?,
+                table_references: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for FromClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.from.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.table_references.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `WHERE` with the condition rows have to satisfy.
+///
+/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#where
+#[derive(Debug)]
+pub struct WhereClause {
+    where_: Where,
+    pub condition: Expression,
+}
+
+impl Parse for WhereClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                where_: input.parse()?,
+                condition: input.parse()?,
+            })
+        })
+    }
+}
+
+impl SqlDisplay for WhereClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.where_.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.condition.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `GROUP BY` with the expressions rows are grouped by.
+///
+/// https://www.postgresql.org/docs/current/queries-table-expressions.html#QUERIES-GROUP
+#[derive(Debug)]
+struct GroupByClause {
+    group: Group,
+    by: By,
+    keys: Punctuated<Expression, Comma, OneOreMany>,
+}
+
+impl Parse for GroupByClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            group: input.parse()?,
+            by: input.parse()?,
+            keys: input.parse()?,
+        })
+    }
+}
+
+impl SqlDisplay for GroupByClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.group.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.by.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.keys.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `HAVING` with the condition groups have to satisfy.
+#[derive(Debug)]
+struct HavingClause {
+    having: Having,
+    condition: Expression,
+}
+
+impl Parse for HavingClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            having: input.parse()?,
+            condition: input.parse()?,
+        })
+    }
+}
+
+impl SqlDisplay for HavingClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.having.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.condition.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `WINDOW` with the named windows that `OVER` can reference.
+///
+/// https://www.postgresql.org/docs/current/sql-select.html#SQL-WINDOW
+#[derive(Debug)]
+struct WindowClause {
+    window: Window,
+    definitions: Punctuated<WindowDefinition, Comma, OneOreMany>,
+}
+
+#[derive(Debug)]
+struct WindowDefinition {
+    name: Identifier,
+    as_: As,
+    specification: WindowSpecification,
+}
+
+impl Parse for WindowClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            window: input.parse()?,
+            definitions: input.parse()?,
+        })
+    }
+}
+
+impl Parse for WindowDefinition {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let as_ = input.parse()?;
+        let (_, specification) = parse_parenthesized(input)?;
+
+        Ok(Self {
+            name,
+            as_,
+            specification,
+        })
+    }
+}
+
+impl WindowClause {
+    fn defines(&self, name: &Identifier) -> bool {
+        self.definitions.0.iter().any(|d| d.name.id == name.id)
+    }
+
+    /// A window can be based on the windows defined before it.
+    fn infer(&self, scope: &Scope, params: &mut Params) -> syn::Result<()> {
+        for (i, definition) in self.definitions.0.iter().enumerate() {
+            let before = || self.definitions.0.iter().take(i);
+
+            if before().any(|d| d.name.id == definition.name.id) {
+                return Err(syn::Error::new(
+                    definition.name.id.span(),
+                    format!("window `{}` is already defined", definition.name.id),
+                ));
+            }
+
+            if let Some(existing) = &definition.specification.existing {
+                if !before().any(|d| d.name.id == existing.id) {
+                    return Err(syn::Error::new(
+                        existing.id.span(),
+                        format!("window `{}` does not exist", existing.id),
+                    ));
+                }
+            }
+
+            definition.specification.infer(scope, params)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for WindowClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.window.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.definitions.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SqlDisplay for WindowDefinition {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.name.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.as_.fmt_sql(f, level)?;
+        write!(f, " (")?;
+        self.specification.fmt_sql(f, level)?;
+        write!(f, ")")
+    }
+}
+
+/// What a grouped query may reference outside of aggregate functions.
+struct Grouping {
+    /// grouping expressions, compared by their SQL
+    expressions: Vec<String>,
+    /// grouping columns as `(table, column)`
+    columns: Vec<(String, String)>,
+}
+
+impl Grouping {
+    /// Group keys are expressions of the input columns, or output column positions and names.
+    fn new(
+        group_by_clause: Option<&GroupByClause>,
+        star_or_sublists: &EitherStarOrPunctSelectSublist,
+        scope: &Scope,
+        params: &mut Params,
+    ) -> syn::Result<Self> {
+        let mut grouping = Self {
+            expressions: Vec::new(),
+            columns: Vec::new(),
+        };
+
+        let keys = match group_by_clause {
+            Some(group_by_clause) => group_by_clause.keys.0.iter().collect(),
+            None => Vec::new(),
+        };
+
+        let sublists = star_or_sublists.sublists();
+
+        for key in keys {
+            if key.contains_aggregate() {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "aggregate functions are not allowed in GROUP BY",
+                ));
+            }
+
+            deny_window_functions(key, "GROUP BY")?;
+
+            match key {
+                Expression::Literal(Literal::Integer(position)) => {
+                    let n: usize = position.base10_parse()?;
+                    let outputs = star_or_sublists.outputs(scope)?;
+
+                    match n.checked_sub(1).and_then(|i| outputs.into_iter().nth(i)) {
+                        Some(Output::Column(table, column, _)) => grouping
+                            .columns
+                            .push((table.name.clone(), column.name.clone())),
+                        Some(Output::Sublist(sublist)) => {
+                            grouping.push(&sublist.expression, scope)?
+                        }
+                        None => return Err(not_in_select_list(position, n)),
+                    }
+                }
+                // input columns take precedence over output columns of the same name
+                Expression::Column(column @ ColumnRef { table: None, .. })
+                    if column.resolve(scope).is_err() =>
+                {
+                    let name = column.column.id.to_string();
+                    match sublists.iter().find(|s| s.name().as_ref() == Some(&name)) {
+                        Some(sublist) => grouping.push(&sublist.expression, scope)?,
+                        None => {
+                            column.resolve(scope)?;
+                        }
+                    }
+                }
+                expression => {
+                    expression.infer(scope, params, None)?;
+                    grouping.push(expression, scope)?;
+                }
+            }
+        }
+
+        Ok(grouping)
+    }
+
+    fn push(&mut self, expression: &Expression, scope: &Scope) -> syn::Result<()> {
+        if let Expression::Column(column) = expression {
+            self.columns.push(column.qualified(scope)?);
+        }
+
+        self.expressions.push(sql_string(expression));
+        Ok(())
+    }
+
+    /// Columns are grouped directly, or by the primary key of their table.
+    fn contains_column(&self, table: &ScopeTable, column: &str) -> bool {
+        let grouped = |column: &str| {
+            self.columns
+                .iter()
+                .any(|(t, c)| *t == table.name && c == column)
+        };
+
+        grouped(column) || (!table.keys.is_empty() && table.keys.iter().all(|key| grouped(key)))
+    }
+
+    /// Checks that `expression` only references grouped columns outside of aggregates.
+    fn check(&self, expression: &Expression, scope: &Scope) -> syn::Result<()> {
+        if self.expressions.contains(&sql_string(expression)) {
+            return Ok(());
+        }
+
+        match expression {
+            Expression::Function(function) if function.is_aggregate() => Ok(()),
+            Expression::Column(column) => {
+                let (table, name) = column.qualified(scope)?;
+
+                // columns of outer queries are constant within a subquery
+                let table = match scope.tables.iter().find(|t| t.name == table) {
+                    Some(table) => table,
+                    None => return Ok(()),
+                };
+
+                if self.contains_column(table, &name) {
+                    Ok(())
+                } else {
+                    Err(syn::Error::new(
+                        column.span(),
+                        not_grouped_message(table, &name),
+                    ))
+                }
+            }
+            expression => expression
+                .children()
+                .into_iter()
+                .try_for_each(|child| self.check(child, scope)),
+        }
+    }
+}
+
+fn not_in_select_list(position: &syn::LitInt, n: usize) -> syn::Error {
+    syn::Error::new(
+        position.span(),
+        format!("GROUP BY position {} is not in select list", n),
+    )
+}
+
+fn not_grouped_message(table: &ScopeTable, column: &str) -> String {
+    format!(
+        "column `{}` must appear in the GROUP BY clause or be used in an aggregate function",
+        table.column_name(column)
+    )
+}
+
+/// `ORDER BY` with one or more sort keys.
+///
+/// https://www.postgresql.org/docs/current/queries-order.html
+#[derive(Debug)]
+struct OrderByClause {
+    order: Order,
+    by: By,
+    sort_keys: Punctuated<SortKey, Comma, OneOreMany>,
+}
+
+impl Parse for OrderByClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            order: input.parse()?,
+            by: input.parse()?,
+            sort_keys: input.parse()?,
+        })
+    }
+}
+
+impl SqlDisplay for OrderByClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.order.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.by.fmt_sql(f, level)?;
+
+        {
+            writeln!(f)?;
+
+            let level = level + 1;
+            indent(f, level)?;
+            self.sort_keys.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An expression to sort by, with its direction and the position of nulls.
+#[derive(Debug)]
+pub struct SortKey {
+    pub expression: Expression,
+    direction: Option<EitherAscOrDesc>,
+    nulls: Option<(Nulls, EitherFirstOrLast)>,
+}
+
+impl Parse for SortKey {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            expression: input.parse()?,
+            direction: try_parse(input, EitherAscOrDesc::parse).ok(),
+            nulls: if peek_keyword(input, "NULLS") {
+                Some((input.parse()?, input.parse()?))
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl SortKey {
+    fn infer(
+        &self,
+        scope: &Scope,
+        params: &mut Params,
+        output: &[String],
+        count: usize,
+    ) -> syn::Result<()> {
+        infer_output_expression(&self.expression, scope, params, output, count, "ORDER BY")
+    }
+}
+
+/// `ORDER BY` and `DISTINCT ON` items are output column names, positions or expressions of the
+/// input columns.
+fn infer_output_expression(
+    expression: &Expression,
+    scope: &Scope,
+    params: &mut Params,
+    output: &[String],
+    count: usize,
+    clause: &str,
+) -> syn::Result<()> {
+    match expression {
+        Expression::Literal(Literal::Integer(position)) => {
+            let n: usize = position.base10_parse()?;
+            if n == 0 || n > count {
+                return Err(syn::Error::new(
+                    position.span(),
+                    format!("{} position {} is not in select list", clause, n),
+                ));
+            }
+        }
+        Expression::Column(ColumnRef {
+            table: None,
+            column,
+        }) if output.contains(&column.id.to_string()) => {}
+        expression => {
+            expression.infer(scope, params, None)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl SqlDisplay for SortKey {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.expression.fmt_sql(f, level)?;
+
+        if let Some(direction) = &self.direction {
+            write!(f, " ")?;
+            direction.fmt_sql(f, level)?;
+        }
+
+        if let Some((nulls, first_or_last)) = &self.nulls {
+            write!(f, " ")?;
+            nulls.fmt_sql(f, level)?;
+            write!(f, " ")?;
+            first_or_last.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `LIMIT` with a row count or `ALL`.
+///
+/// https://www.postgresql.org/docs/current/queries-limit.html
+#[derive(Debug)]
+struct LimitClause {
+    limit: Limit,
+    count: EitherAllOrExpression,
+}
+
+impl Parse for LimitClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            limit: input.parse()?,
+            count: input.parse()?,
+        })
+    }
+}
+
+impl SqlDisplay for LimitClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.limit.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.count.fmt_sql(f, level)
+    }
+}
+
+/// `OFFSET` with the number of rows to skip.
+#[derive(Debug)]
+struct OffsetClause {
+    offset: Offset,
+    start: Expression,
+    rows: Option<EitherRowsOrRow>,
+}
+
+impl Parse for OffsetClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            offset: input.parse()?,
+            start: input.parse()?,
+            rows: try_parse(input, EitherRowsOrRow::parse).ok(),
+        })
+    }
+}
+
+impl SqlDisplay for OffsetClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.offset.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.start.fmt_sql(f, level)?;
+
+        if let Some(rows) = &self.rows {
+            write!(f, " ")?;
+            rows.fmt_sql(f, level)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `FETCH FIRST n ROWS ONLY`, the standard spelling of `LIMIT`.
+///
+/// https://www.postgresql.org/docs/current/sql-select.html#SQL-LIMIT
+#[derive(Debug)]
+struct FetchClause {
+    fetch: Fetch,
+    first_or_next: EitherFirstOrNext,
+    count: Option<Expression>,
+    rows: EitherRowsOrRow,
+    only: Only,
+}
+
+impl Parse for FetchClause {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            fetch: input.parse()?,
+            first_or_next: input.parse()?,
+            count: if peek_keyword(input, "ROW") || peek_keyword(input, "ROWS") {
+                None
+            } else {
+                Some(input.parse()?)
+            },
+            rows: input.parse()?,
+            only: input.parse()?,
+        })
+    }
+}
+
+impl SqlDisplay for FetchClause {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.fetch.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.first_or_next.fmt_sql(f, level)?;
+
+        if let Some(count) = &self.count {
+            write!(f, " ")?;
+            count.fmt_sql(f, level)?;
+        }
+
+        write!(f, " ")?;
+        self.rows.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.only.fmt_sql(f, level)
+    }
+}
+
+///
+///
+/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#tableReference
+#[derive(Debug)]
+struct TableReference {
+    joined_table: JoinedTable,
+}
+
+impl Parse for TableReference {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        try_parse(input, |input| {
+            Ok(Self {
+                joined_table: input.parse()?,
+            })
+        })
     }
 }
 
-impl Display for SqlStatement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        SqlDisplay::fmt_sql(&self.query, f, 0)
+impl SqlDisplay for TableReference {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        self.joined_table.fmt_sql(f, level)
     }
 }
 
 ///
 ///
-///
+/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#joinedTable
 #[derive(Debug)]
-struct Query {
-    select_clause: SelectClause,
-    into_clause: Option<IntoClause>,
-    rest: Option<(
-        FromClause,
-        Option<()>, // where
-        Option<()>, // group by
-        Option<()>, // having
-    )>,
+struct JoinedTable {
+    table_primary: TablePrimary,
+    joins: Vec<TableJoin>,
 }
 
-impl Parse for Query {
+impl Parse for JoinedTable {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         try_parse(input, |input| {
+            let table_primary = input.parse()?;
+
+            let mut joins = Vec::new();
+            while TableJoin::peek(input) {
+                joins.push(input.parse()?);
+            }
+
             Ok(Self {
-                select_clause: input.parse()?,
-                into_clause: input.parse().ok(),
-                rest: try_parse(input, |input| Ok((input.parse()?, None, None, None))).ok(),
+                table_primary,
+                joins,
             })
         })
     }
 }
 
-impl ProjectionWalker for Query {
-    fn walk_projection(&self, schema: &Schema, f: &mut dyn FnMut(&Projection)) {
-        match &self.select_clause.star_or_sublists {
-            EitherStarOrPunctSelectSublist::Star(_) => {
-                let (from, _, _, _) = self.rest.as_ref().expect("* with not table is not valid");
-                for tr in &from.table_references.0 {
-                    let name = tr
-                        .joined_table
-                        .table_primary
-                        .table_name
-                        .identifier
-                        .id
-                        .to_string();
-                    if let Some(table) = schema.tables.iter().find(|t| t.table == name) {
-                        for field in &table.def {
-                            let field_name = field.name.to_string();
-                            f(&Projection {
-                                name: format!("{}.{}", name, field_name),
-                                diesel_type: &field.ty,
-                            })
-                        }
-                    } else {
-                        panic!("cannot find table {}", name);
-                    }
-                }
-            }
-            EitherStarOrPunctSelectSublist::PunctSelectSublist(_) => {}
+impl JoinedTable {
+    /// The tables of the joins, from left to right. `preceding` are the tables before in `FROM`.
+    fn scope_tables(
+        &self,
+        outer: &Scope,
+        preceding: &[ScopeTable],
+        params: &mut Params,
+    ) -> syn::Result<Vec<ScopeTable>> {
+        let mut tables = vec![self.table_primary.scope_table(outer, preceding, params)?];
+
+        for join in &self.joins {
+            let visible: Vec<_> = preceding.iter().chain(&tables).cloned().collect();
+            let right = join.table_primary.scope_table(outer, &visible, params)?;
+            tables = join.join(outer, tables, right, params)?;
         }
-    }
-}
 
-impl Display for Query {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        SqlDisplay::fmt_sql(self, f, 0)
+        Ok(tables)
     }
 }
 
-impl SqlDisplay for Query {
+impl SqlDisplay for JoinedTable {
     fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        indent(f, level)?;
-        self.select_clause.fmt_sql(f, level)?;
-
-        if let Some(into) = &self.into_clause {
-            writeln!(f)?;
-            indent(f, level)?;
-            into.fmt_sql(f, level)?;
-        }
+        self.table_primary.fmt_sql(f, level)?;
 
-        if let Some((from, _, _, _)) = &self.rest {
+        for join in &self.joins {
             writeln!(f)?;
             indent(f, level)?;
-            from.fmt_sql(f, level)?;
+            join.fmt_sql(f, level)?;
         }
 
         Ok(())
     }
 }
 
+/// A join with the table to its left in `FROM`.
 ///
-///
-///
+/// https://www.postgresql.org/docs/current/queries-table-expressions.html#QUERIES-JOIN
 #[derive(Debug)]
-struct SelectClause {
-    select: Select,
-    all_or_distinct: Option<EitherAllOrDistinct>,
-    star_or_sublists: EitherStarOrPunctSelectSublist,
+struct TableJoin {
+    natural: Option<Natural>,
+    join_type: JoinType,
+    join: Join,
+    table_primary: TablePrimary,
+    condition: Option<JoinCondition>,
 }
 
-impl Parse for SelectClause {
+const JOIN_KEYWORDS: &[&str] = &["CROSS", "FULL", "INNER", "JOIN", "LEFT", "NATURAL", "RIGHT"];
+
+impl Parse for TableJoin {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        try_parse(input, |input| {
-            Ok(Self {
-                select: input.parse()?,
-                all_or_distinct: input.parse().ok(),
-                star_or_sublists: input.parse()?,
-            })
+        let natural: Option<Natural> = parse_if_keyword(input, "NATURAL")?;
+        let join_type = input.parse()?;
+        let join = input.parse()?;
+        let table_primary = input.parse()?;
+
+        let condition = match (&natural, &join_type) {
+            (Some(_), _) | (_, JoinType::Cross(_)) => None,
+            _ => Some(input.parse()?),
+        };
+
+        Ok(Self {
+            natural,
+            join_type,
+            join,
+            table_primary,
+            condition,
         })
     }
 }
 
-impl SqlDisplay for SelectClause {
+impl TableJoin {
+    fn peek(input: syn::parse::ParseStream) -> bool {
+        JOIN_KEYWORDS
+            .iter()
+            .any(|keyword| peek_keyword(input, keyword))
+    }
+
+    /// The tables of the left side joined with the right table, nullable where rows can be
+    /// missing. Columns merged by `USING` or `NATURAL` precede the other columns, like in `*`.
+    fn join(
+        &self,
+        outer: &Scope,
+        mut left: Vec<ScopeTable>,
+        mut right: ScopeTable,
+        params: &mut Params,
+    ) -> syn::Result<Vec<ScopeTable>> {
+        let names = match &self.condition {
+            Some(JoinCondition::On(_, condition)) => {
+                if condition.contains_aggregate() {
+                    return Err(syn::Error::new(
+                        condition.span(),
+                        "aggregate functions are not allowed in JOIN conditions",
+                    ));
+                }
+
+                deny_window_functions(condition, "JOIN conditions")?;
+
+                let mut scope = outer.nested();
+                for table in left.iter().chain(std::iter::once(&right)) {
+                    scope.push(table.clone());
+                }
+
+                condition.infer_condition(&scope, params)?;
+                Vec::new()
+            }
+            Some(JoinCondition::Using(_, columns)) => {
+                let mut names: Vec<syn::Ident> = Vec::new();
+                for column in &columns.0 {
+                    if names.contains(&column.id) {
+                        return Err(syn::Error::new(
+                            column.id.span(),
+                            format!(
+                                "column `{}` appears more than once in USING clause",
+                                column.id
+                            ),
+                        ));
+                    }
+
+                    names.push(column.id.clone());
+                }
+
+                names
+            }
+            None if self.natural.is_some() => left
+                .iter()
+                .flat_map(|t| t.unqualified_columns())
+                .filter(|c| right.unqualified_columns().any(|r| r.name == c.name))
+                .map(|c| syn::Ident::new(&c.name, proc_macro2::Span::call_site()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mut merged = Vec::new();
+        for name in &names {
+            let (l, left_column) = join_column(&left, name, "left")?;
+            let (_, right_column) = join_column(std::slice::from_ref(&right), name, "right")?;
+
+            let ty = match (&left_column.ty.ty, &right_column.ty.ty) {
+                (SqlType::Unknown, ty) | (ty, SqlType::Unknown) => ty.clone(),
+                (SqlType::Named(a), SqlType::Named(b)) => match builtin::common_type(a, b) {
+                    Some(ty) => SqlType::named(ty),
+                    None => {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            format!("JOIN/USING types {} and {} cannot be matched", a, b),
+                        ))
+                    }
+                },
+            };
+
+            let (left_nullable, right_nullable) =
+                (left_column.ty.nullable, right_column.ty.nullable);
+            let nullable = match self.join_type {
+                JoinType::Left(..) => left_nullable,
+                JoinType::Right(..) => right_nullable,
+                _ => left_nullable && right_nullable,
+            };
+
+            merged.push(ScopeColumn {
+                name: name.to_string(),
+                ty: ColumnType::new(ty, nullable),
+            });
+
+            left[l].merged.push(name.to_string());
+            right.merged.push(name.to_string());
+        }
+
+        // the side opposite of an outer join is `NULL` for rows without a match
+        let (left_missing, right_missing) = match self.join_type {
+            JoinType::Left(..) => (false, true),
+            JoinType::Right(..) => (true, false),
+            JoinType::Full(..) => (true, true),
+            _ => (false, false),
+        };
+
+        if left_missing {
+            left.iter_mut().for_each(make_nullable);
+        }
+
+        if right_missing {
+            make_nullable(&mut right);
+        }
+
+        let mut tables = Vec::new();
+        if !merged.is_empty() {
+            tables.push(ScopeTable::join_columns(merged));
+        }
+
+        tables.extend(left);
+        tables.push(right);
+        Ok(tables)
+    }
+}
+
+/// The table index and column of a join column, which has to be unique on its side.
+fn join_column<'a>(
+    tables: &'a [ScopeTable],
+    name: &syn::Ident,
+    side: &str,
+) -> syn::Result<(usize, &'a ScopeColumn)> {
+    let mut found = tables.iter().enumerate().flat_map(|(i, t)| {
+        t.unqualified_columns()
+            .filter(|c| *name == c.name)
+            .map(move |c| (i, c))
+    });
+
+    match (found.next(), found.next()) {
+        (Some(found), None) => Ok(found),
+        (Some(_), Some(_)) => Err(syn::Error::new(
+            name.span(),
+            format!(
+                "common column name `{}` appears more than once in {} table",
+                name, side
+            ),
+        )),
+        (None, _) => Err(syn::Error::new(
+            name.span(),
+            format!(
+                "column `{}` specified in USING clause does not exist in {} table",
+                name, side
+            ),
+        )),
+    }
+}
+
+fn make_nullable(table: &mut ScopeTable) {
+    for column in &mut table.columns {
+        column.ty.nullable = true;
+    }
+}
+
+impl SqlDisplay for TableJoin {
     fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        self.select.fmt_sql(f, level)?;
+        if let Some(natural) = &self.natural {
+            natural.fmt_sql(f, level)?;
+            write!(f, " ")?;
+        }
 
-        if let Some(v) = &self.all_or_distinct {
+        if self.join_type.fmt_sql(f, level)? {
             write!(f, " ")?;
-            v.fmt_sql(f, level)?;
         }
 
-        {
-            let level = level + 1;
-            writeln!(f)?;
-            indent(f, level)?;
-            self.star_or_sublists.fmt_sql(f, level)?;
+        self.join.fmt_sql(f, level)?;
+        write!(f, " ")?;
+        self.table_primary.fmt_sql(f, level)?;
+
+        if let Some(condition) = &self.condition {
+            write!(f, " ")?;
+            condition.fmt_sql(f, level)?;
         }
 
         Ok(())
     }
 }
 
-///
-///
-///
+/// The kind of a join, which decides the rows kept without a match.
 #[derive(Debug)]
-struct SelectSublist;
+enum JoinType {
+    Cross(Cross),
+    Inner(Option<Inner>),
+    Left(Left, Option<Outer>),
+    Right(Right, Option<Outer>),
+    Full(Full, Option<Outer>),
+}
 
-impl Parse for SelectSublist {
+impl Parse for JoinType {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        panic!("{:?}", input);
+        if peek_keyword(input, "CROSS") {
+            Ok(Self::Cross(input.parse()?))
+        } else if peek_keyword(input, "LEFT") {
+            Ok(Self::Left(
+                input.parse()?,
+                parse_if_keyword(input, "OUTER")?,
+            ))
+        } else if peek_keyword(input, "RIGHT") {
+            Ok(Self::Right(
+                input.parse()?,
+                parse_if_keyword(input, "OUTER")?,
+            ))
+        } else if peek_keyword(input, "FULL") {
+            Ok(Self::Full(
+                input.parse()?,
+                parse_if_keyword(input, "OUTER")?,
+            ))
+        } else {
+            Ok(Self::Inner(parse_if_keyword(input, "INNER")?))
+        }
     }
 }
 
-impl SqlDisplay for SelectSublist {
-    fn fmt_sql(&self, _f: &mut std::fmt::Formatter<'_>, _level: usize) -> std::fmt::Result {
-        Ok(())
+impl JoinType {
+    /// Returns whether anything was written, a plain `JOIN` has no join type.
+    fn fmt_sql(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        level: usize,
+    ) -> Result<bool, std::fmt::Error> {
+        let outer = match self {
+            Self::Cross(cross) => {
+                cross.fmt_sql(f, level)?;
+                None
+            }
+            Self::Inner(None) => return Ok(false),
+            Self::Inner(Some(inner)) => {
+                inner.fmt_sql(f, level)?;
+                None
+            }
+            Self::Left(left, outer) => {
+                left.fmt_sql(f, level)?;
+                outer.as_ref()
+            }
+            Self::Right(right, outer) => {
+                right.fmt_sql(f, level)?;
+                outer.as_ref()
+            }
+            Self::Full(full, outer) => {
+                full.fmt_sql(f, level)?;
+                outer.as_ref()
+            }
+        };
+
+        if let Some(outer) = outer {
+            write!(f, " ")?;
+            outer.fmt_sql(f, level)?;
+        }
+
+        Ok(true)
     }
 }
 
-///
-///
-///
+/// How the rows of a join are matched, `ON` a condition or `USING` shared columns.
 #[derive(Debug)]
-struct IntoClause {
-    into: Into,
-    identifier: Identifier,
+enum JoinCondition {
+    On(On, Expression),
+    Using(Using, PunctIdentifier),
 }
 
-impl Parse for IntoClause {
+impl Parse for JoinCondition {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        try_parse(input, |input| {
-            Ok(Self {
-                into: input.parse()?,
-                identifier: input.parse()?,
-            })
-        })
+        if peek_keyword(input, "USING") {
+            let using = input.parse()?;
+            let (_, columns) = parse_parenthesized(input)?;
+            return Ok(Self::Using(using, columns));
+        }
+
+        match input.parse::<On>() {
+            Ok(on) => Ok(Self::On(on, input.parse()?)),
+            Err(_) => Err(input.error("expected `ON` or `USING` after JOIN")),
+        }
     }
 }
 
-impl SqlDisplay for IntoClause {
-    fn fmt_sql(&self, _f: &mut std::fmt::Formatter<'_>, _level: usize) -> std::fmt::Result {
-        todo!()
+impl SqlDisplay for JoinCondition {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::On(on, condition) => {
+                on.fmt_sql(f, level)?;
+                write!(f, " ")?;
+                condition.fmt_sql(f, level)
+            }
+            Self::Using(using, columns) => {
+                using.fmt_sql(f, level)?;
+                write!(f, " (")?;
+                columns.fmt_sql(f, level)?;
+                write!(f, ")")
+            }
+        }
     }
 }
 
 ///
 ///
-/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#from
+/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#tablePrimary
 #[derive(Debug)]
-struct FromClause {
-    from: From,
-    table_references: Punctuated<TableReference, Comma, OneOreMany>,
+enum TablePrimary {
+    TableName(TableName),
+    DerivedTable(DerivedTable),
+    TableFunction(TableFunction),
 }
 
-impl Parse for FromClause {
+impl Parse for TablePrimary {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        try_parse(input, |input| {
-            Ok(Self {
-                from: input.parse()?,
-                table_references: input.parse()?,
-            })
-        })
+        let fork = input.fork();
+        let lateral = parse_keyword(&fork, "LATERAL").is_ok();
+
+        if fork.peek(Paren) {
+            Ok(Self::DerivedTable(input.parse()?))
+        } else if fork.peek(syn::Ident) && fork.peek2(Paren) {
+            Ok(Self::TableFunction(input.parse()?))
+        } else if lateral {
+            Err(fork.error("expected a subquery or function after LATERAL"))
+        } else {
+            Ok(Self::TableName(input.parse()?))
+        }
     }
 }
 
-impl SqlDisplay for FromClause {
-    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        self.from.fmt_sql(f, level)?;
+impl TablePrimary {
+    /// `outer` holds the tables of the outer queries, `preceding` the tables before in `FROM`,
+    /// which only `LATERAL` subqueries and functions can reference.
+    fn scope_table(
+        &self,
+        outer: &Scope,
+        preceding: &[ScopeTable],
+        params: &mut Params,
+    ) -> syn::Result<ScopeTable> {
+        match self {
+            Self::TableName(table_name) => match outer.cte(&table_name.identifier.id) {
+                Some(cte) => Ok(ScopeTable {
+                    name: table_name.name(),
+                    ..cte.clone()
+                }),
+                None => {
+                    let table = outer.schema.table(&table_name.identifier.id)?;
+                    Ok(ScopeTable::from_definition(table_name.name(), table))
+                }
+            },
+            Self::DerivedTable(derived_table) => match derived_table.lateral {
+                Some(_) => derived_table.scope_table(&lateral_scope(outer, preceding), params),
+                None => derived_table.scope_table(outer, params),
+            },
+            Self::TableFunction(function) => {
+                function.scope_table(&lateral_scope(outer, preceding), params)
+            }
+        }
+    }
+}
 
-        {
-            writeln!(f)?;
+fn lateral_scope<'a>(outer: &'a Scope, preceding: &[ScopeTable]) -> Scope<'a> {
+    let mut scope = outer.nested();
+    for table in preceding {
+        scope.push(table.clone());
+    }
 
-            let level = level + 1;
-            indent(f, level)?;
-            self.table_references.fmt_sql(f, level)?;
-        }
+    scope
+}
 
-        Ok(())
+impl SqlDisplay for TablePrimary {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+        match self {
+            Self::TableName(table_name) => table_name.fmt_sql(f, level),
+            Self::DerivedTable(derived_table) => derived_table.fmt_sql(f, level),
+            Self::TableFunction(function) => function.fmt_sql(f, level),
+        }
     }
 }
 
+/// A subquery in `FROM`, its output columns are the columns of the alias.
 ///
-///
-/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#tableReference
+/// https://www.postgresql.org/docs/current/queries-table-expressions.html#QUERIES-SUBQUERIES
 #[derive(Debug)]
-struct TableReference {
-    joined_table: JoinedTable,
+struct DerivedTable {
+    lateral: Option<Lateral>,
+    query: Box<Query>,
+    as_: Option<As>,
+    alias: Identifier,
+    column_aliases: Option<(Paren, PunctIdentifier)>,
 }
 
-impl Parse for TableReference {
+impl Parse for DerivedTable {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        try_parse(input, |input| {
-            Ok(Self {
-                joined_table: input.parse()?,
-            })
+        let lateral = parse_if_keyword(input, "LATERAL")?;
+        let (paren, query) = parse_parenthesized(input)?;
+        let as_ = input.parse().ok();
+
+        let alias = match input.parse() {
+            Ok(alias) => alias,
+            Err(_) => {
+                return Err(syn::Error::new(
+                    paren.span,
+                    "subquery in FROM must have an alias, add `AS name`",
+                ))
+            }
+        };
+
+        Ok(Self {
+            lateral,
+            query: Box::new(query),
+            as_,
+            alias,
+            column_aliases: if input.peek(Paren) {
+                Some(parse_parenthesized(input)?)
+            } else {
+                None
+            },
         })
     }
 }
 
-impl SqlDisplay for TableReference {
-    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        self.joined_table.fmt_sql(f, level)
+impl DerivedTable {
+    fn scope_table(&self, outer: &Scope, params: &mut Params) -> syn::Result<ScopeTable> {
+        let projections = self.query.columns(outer, params)?;
+        query_table(&self.alias, projections, &self.column_aliases)
     }
 }
 
+/// A set-returning function in `FROM`, a table of its single column.
 ///
-///
-/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#joinedTable
+/// https://www.postgresql.org/docs/current/queries-table-expressions.html#QUERIES-TABLEFUNCTIONS
 #[derive(Debug)]
-struct JoinedTable {
-    table_primary: TablePrimary,
-    // todo cross_join ...
+struct TableFunction {
+    lateral: Option<Lateral>,
+    function: FunctionCall,
+    as_: Option<As>,
+    alias: Option<Identifier>,
+    column_aliases: Option<(Paren, PunctIdentifier)>,
 }
 
-impl Parse for JoinedTable {
+impl Parse for TableFunction {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        try_parse(input, |input| {
-            Ok(Self {
-                table_primary: input.parse()?,
-            })
+        let lateral = parse_if_keyword(input, "LATERAL")?;
+        let function = input.parse()?;
+        let as_: Option<As> = input.parse().ok();
+        let alias = match as_ {
+            Some(_) => Some(input.parse()?),
+            None => input.parse().ok(),
+        };
+
+        Ok(Self {
+            lateral,
+            function,
+            as_,
+            column_aliases: if alias.is_some() && input.peek(Paren) {
+                Some(parse_parenthesized(input)?)
+            } else {
+                None
+            },
+            alias,
         })
     }
 }
 
-impl SqlDisplay for JoinedTable {
+impl TableFunction {
+    /// Functions can always reference the preceding tables, like with `LATERAL`.
+    fn scope_table(&self, scope: &Scope, params: &mut Params) -> syn::Result<ScopeTable> {
+        let ty = self.function.infer_set_returning(scope, params)?;
+        let name = self.alias.as_ref().unwrap_or(&self.function.name);
+
+        // the column is named after the table alias, like the table is
+        let column = match builtin::output_column(&self.function.function_name()) {
+            Some(column) => column.to_owned(),
+            None => name.id.to_string(),
+        };
+
+        let projection = Projection {
+            name: Some(column),
+            ty,
+            span: self.function.name.id.span(),
+            json: None,
+        };
+
+        query_table(name, vec![projection], &self.column_aliases)
+    }
+}
+
+impl SqlDisplay for TableFunction {
     fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        self.table_primary.fmt_sql(f, level)
+        if let Some(lateral) = &self.lateral {
+            lateral.fmt_sql(f, level)?;
+            write!(f, " ")?;
+        }
+
+        self.function.fmt_sql(f, level)?;
+
+        if let Some(alias) = &self.alias {
+            if let Some(as_) = &self.as_ {
+                write!(f, " ")?;
+                as_.fmt_sql(f, level)?;
+            }
+
+            write!(f, " ")?;
+            alias.fmt_sql(f, level)?;
+            fmt_column_aliases(&self.column_aliases, f, level)?;
+        }
+
+        Ok(())
     }
 }
 
-///
-///
-/// https://teiid.github.io/teiid-documents/9.0.x/content/reference/BNF_for_SQL_Grammar.html#tablePrimary
-#[derive(Debug)]
-struct TablePrimary {
-    table_name: TableName,
+/// A table of the output columns of a query, optionally renamed by column aliases.
+fn query_table(
+    name: &Identifier,
+    projections: Vec<Projection>,
+    column_aliases: &Option<(Paren, PunctIdentifier)>,
+) -> syn::Result<ScopeTable> {
+    let aliases = match column_aliases {
+        Some((paren, aliases)) => {
+            if aliases.0.len() > projections.len() {
+                return Err(syn::Error::new(
+                    paren.span,
+                    format!(
+                        "`{}` has {} columns available but {} columns specified",
+                        name.id,
+                        projections.len(),
+                        aliases.0.len()
+                    ),
+                ));
+            }
+
+            aliases.0.iter().map(|alias| alias.id.to_string()).collect()
+        }
+        None => Vec::new(),
+    };
+
+    let mut columns = Vec::new();
+    for (i, projection) in projections.into_iter().enumerate() {
+        let name = match aliases.get(i).or(projection.name.as_ref()) {
+            Some(name) => name.clone(),
+            None => {
+                return Err(syn::Error::new(
+                    projection.span,
+                    "expression in a subquery needs a name to be referenced, add `AS name`",
+                ))
+            }
+        };
+
+        if columns.iter().any(|c: &ScopeColumn| c.name == name) {
+            return Err(syn::Error::new(
+                projection.span,
+                format!("column `{}` specified more than once", name),
+            ));
+        }
+
+        columns.push(ScopeColumn {
+            name,
+            ty: projection.ty,
+        });
+    }
+
+    Ok(ScopeTable {
+        name: name.id.to_string(),
+        columns,
+        keys: Vec::new(),
+        merged: Vec::new(),
+    })
 }
 
-impl Parse for TablePrimary {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        try_parse(input, |input| {
-            Ok(Self {
-                table_name: input.parse()?,
-            })
-        })
+fn fmt_column_aliases(
+    column_aliases: &Option<(Paren, PunctIdentifier)>,
+    f: &mut std::fmt::Formatter<'_>,
+    level: usize,
+) -> std::fmt::Result {
+    if let Some((_, column_aliases)) = column_aliases {
+        write!(f, "(")?;
+        column_aliases.fmt_sql(f, level)?;
+        write!(f, ")")?;
     }
+
+    Ok(())
 }
 
-impl SqlDisplay for TablePrimary {
+impl SqlDisplay for DerivedTable {
     fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
-        self.table_name.fmt_sql(f, level)
+        if let Some(lateral) = &self.lateral {
+            lateral.fmt_sql(f, level)?;
+            write!(f, " ")?;
+        }
+
+        writeln!(f, "(")?;
+        self.query.fmt_sql(f, level + 1)?;
+        writeln!(f)?;
+        indent(f, level)?;
+        write!(f, ")")?;
+
+        if let Some(as_) = &self.as_ {
+            write!(f, " ")?;
+            as_.fmt_sql(f, level)?;
+        }
+
+        write!(f, " ")?;
+        self.alias.fmt_sql(f, level)?;
+        fmt_column_aliases(&self.column_aliases, f, level)
     }
 }
 
@@ -360,6 +2980,16 @@ impl Parse for TableName {
     }
 }
 
+impl TableName {
+    /// The name columns are qualified with, the alias if there is one.
+    fn name(&self) -> String {
+        match &self.as_ {
+            Some((_, alias)) => alias.id.to_string(),
+            None => self.identifier.id.to_string(),
+        }
+    }
+}
+
 impl SqlDisplay for TableName {
     fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
         self.identifier.fmt_sql(f, level)?;
@@ -370,6 +3000,7 @@ impl SqlDisplay for TableName {
                 as_.fmt_sql(f, level)?;
             }
 
+            write!(f, " ")?;
             id.fmt_sql(f, level)?;
         }
 
@@ -377,23 +3008,79 @@ impl SqlDisplay for TableName {
     }
 }
 
+impl_token_ident!(With, "WITH");
+impl_token_ident!(Recursive, "RECURSIVE");
 impl_token_ident!(Select, "SELECT");
-impl_token_ident!(All, "ALL");
-impl_token_ident!(Distinct, "DISTINCT");
-impl_token_ident!(Into, "INTO");
-impl_token_ident!(From, "FROM");
-impl_token_ident!(As, "AS");
+impl_token_ident!(pub, All, "ALL");
+impl_token_ident!(pub, Distinct, "DISTINCT");
+impl_token_ident!(pub, Into, "INTO");
+impl_token_ident!(pub, From, "FROM");
+impl_token_ident!(Where, "WHERE");
+impl_token_ident!(pub, As, "AS");
+impl_token_ident!(Group, "GROUP");
+impl_token_ident!(Having, "HAVING");
+impl_token_ident!(Window, "WINDOW");
+impl_token_ident!(pub, On, "ON");
+impl_token_ident!(Join, "JOIN");
+impl_token_ident!(Cross, "CROSS");
+impl_token_ident!(Natural, "NATURAL");
+impl_token_ident!(Inner, "INNER");
+impl_token_ident!(Left, "LEFT");
+impl_token_ident!(Right, "RIGHT");
+impl_token_ident!(Full, "FULL");
+impl_token_ident!(Outer, "OUTER");
+impl_token_ident!(Using, "USING");
+impl_token_ident!(Lateral, "LATERAL");
+impl_token_ident!(pub, Order, "ORDER");
+impl_token_ident!(pub, By, "BY");
+impl_token_ident!(Asc, "ASC");
+impl_token_ident!(Desc, "DESC");
+impl_token_ident!(Nulls, "NULLS");
+impl_token_ident!(First, "FIRST");
+impl_token_ident!(Last, "LAST");
+impl_token_ident!(Limit, "LIMIT");
+impl_token_ident!(Offset, "OFFSET");
+impl_token_ident!(Fetch, "FETCH");
+impl_token_ident!(Next, "NEXT");
+impl_token_ident!(Rows, "ROWS");
+impl_token_ident!(Row, "ROW");
+impl_token_ident!(Only, "ONLY");
+
+impl_token_punct!(pub, Comma, ,);
+
+/// `*`, keeps its span for errors about the columns it selects.
+#[derive(Debug)]
+pub struct Star {
+    pub span: proc_macro2::Span,
+}
+
+impl Parse for Star {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let star: Token![*] = input.parse()?;
+        Ok(Self { span: star.span })
+    }
+}
 
-impl_token_punct!(Star, *);
-impl_token_punct!(Comma, ,);
+impl SqlDisplay for Star {
+    fn fmt_sql(&self, f: &mut std::fmt::Formatter<'_>, _: usize) -> std::fmt::Result {
+        write!(f, "*")
+    }
+}
 
-type PunctSelectSublist = Punctuated<SelectSublist, Comma>;
-impl_either!(Star, PunctSelectSublist);
+type PunctSelectSublist = Punctuated<EitherQualifiedStarOrSelectSublist, Comma>;
+type PunctIdentifier = Punctuated<Identifier, Comma, OneOreMany>;
+impl_either!(pub, Star, PunctSelectSublist);
+impl_either!(pub, QualifiedStar, SelectSublist);
 impl_either!(All, Distinct);
+impl_either!(All, Expression);
+impl_either!(Asc, Desc);
+impl_either!(First, Last);
+impl_either!(First, Next);
+impl_either!(Rows, Row);
 
 // tokens & helper structs
 
-struct Punctuated<T, P, S = NoneOrMany>(syn::punctuated::Punctuated<T, P>, PhantomData<S>);
+pub struct Punctuated<T, P, S = NoneOrMany>(pub syn::punctuated::Punctuated<T, P>, PhantomData<S>);
 
 impl<T: Parse, P: Parse, S: PunctuationStrategy<T, P>> Parse for Punctuated<T, P, S> {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
@@ -432,36 +3119,49 @@ impl<T: SqlDisplay, P: SqlDisplay, S> SqlDisplay for Punctuated<T, P, S> {
     }
 }
 
-trait PunctuationStrategy<T, P> {
+pub trait PunctuationStrategy<T, P> {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<syn::punctuated::Punctuated<T, P>>;
 }
 
-struct NoneOrMany;
+pub struct NoneOrMany;
 
 impl<T: Parse, P: Parse> PunctuationStrategy<T, P> for NoneOrMany {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<syn::punctuated::Punctuated<T, P>> {
-        syn::punctuated::Punctuated::parse_terminated(input)
+        match try_parse(input, T::parse) {
+            Ok(first) => parse_punctuated_rest(input, first),
+            Err(_) => Ok(syn::punctuated::Punctuated::new()),
+        }
     }
 }
 
-struct OneOreMany;
+pub struct OneOreMany;
 
 impl<T: Parse, P: Parse> PunctuationStrategy<T, P> for OneOreMany {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<syn::punctuated::Punctuated<T, P>> {
-        let result: syn::punctuated::Punctuated<T, P> =
-            syn::punctuated::Punctuated::parse_terminated(input)?;
+        let first = input.parse()?;
+        parse_punctuated_rest(input, first)
+    }
+}
 
-        if result.is_empty() {
-            Err(input.error("empty not allowed"))
-        } else {
-            Ok(result)
-        }
+/// Parses `, value` pairs until there is no more punctuation.
+fn parse_punctuated_rest<T: Parse, P: Parse>(
+    input: syn::parse::ParseStream,
+    first: T,
+) -> syn::Result<syn::punctuated::Punctuated<T, P>> {
+    let mut result = syn::punctuated::Punctuated::new();
+    result.push_value(first);
+
+    while let Ok(punct) = try_parse(input, P::parse) {
+        result.push_punct(punct);
+        result.push_value(input.parse()?);
     }
+
+    Ok(result)
 }
 
 // helper
 
-fn indent(f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
+pub fn indent(f: &mut std::fmt::Formatter<'_>, level: usize) -> std::fmt::Result {
     let ident = 4 * level;
     write!(f, "{:ident$}", "")
 }
@@ -0,0 +1,1494 @@
+use super::{
+    column_type_to_param_ty, did_you_mean, projections_to_fields,
+    schema::Schema,
+    scope::{ColumnType, Params},
+    sql::{Cardinality, ProjectionWalker, SqlStatement},
+    sql_type_to_ty, Sql,
+};
+use quote::quote;
+
+const SCHEMA: &str = r#"
+table! {
+    pupil (id) {
+        id -> Int4,
+        name -> Text,
+        email -> Nullable<Text>,
+        teacher_id -> Nullable<Int4>,
+        score -> Int4,
+        created -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    teacher (id) {
+        id -> Int4,
+        name -> Text,
+        active -> Bool,
+    }
+}
+
+table! {
+    enrollment (pupil_id, course) {
+        pupil_id -> Int4,
+        course -> Text,
+    }
+}
+
+table! {
+    setting (id) {
+        id -> Int4,
+        data -> Jsonb,
+        extra -> Nullable<Json>,
+    }
+}
+"#;
+
+fn schema() -> Schema {
+    syn::parse_str(SCHEMA).unwrap()
+}
+
+fn statement(sql: &str) -> SqlStatement {
+    syn::parse_str(&format!("-- \"schema.rs\" Case {}", sql)).unwrap()
+}
+
+/// Types like `Nullable<Int4>`, to compare them with the schema.
+fn type_name(ty: &ColumnType) -> String {
+    if ty.nullable {
+        format!("Nullable<{}>", ty.ty)
+    } else {
+        ty.ty.to_string()
+    }
+}
+
+/// Output column names with their types.
+type Columns = Vec<(String, String)>;
+
+/// The output columns and parameter types of a statement.
+fn infer(sql: &str) -> syn::Result<(Columns, Vec<String>)> {
+    let mut params = Params::default();
+    let mut columns = Vec::new();
+    statement(sql).walk_projection(&schema(), &mut params, &mut |p| {
+        columns.push((p.name.clone().unwrap_or_default(), type_name(&p.ty)))
+    })?;
+    let params = params.finish()?;
+
+    Ok((
+        columns,
+        params.iter().map(|(_, ty)| type_name(ty)).collect(),
+    ))
+}
+
+fn columns(sql: &str) -> Columns {
+    infer(sql).unwrap().0
+}
+
+fn params(sql: &str) -> Vec<String> {
+    infer(sql).unwrap().1
+}
+
+fn error(sql: &str) -> String {
+    infer(sql).unwrap_err().to_string()
+}
+
+fn parse_error(sql: &str) -> String {
+    syn::parse_str::<SqlStatement>(&format!("-- \"schema.rs\" Case {}", sql))
+        .unwrap_err()
+        .to_string()
+}
+
+fn warnings(sql: &str) -> Vec<String> {
+    let mut params = Params::default();
+    statement(sql)
+        .walk_projection(&schema(), &mut params, &mut |_| {})
+        .unwrap();
+
+    params
+        .take_warnings()
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect()
+}
+
+fn pairs(expected: &[(&str, &str)]) -> Columns {
+    expected
+        .iter()
+        .map(|(name, ty)| (name.to_string(), ty.to_string()))
+        .collect()
+}
+
+#[test]
+fn unknown_names_suggest_similar_ones() {
+    assert_eq!(
+        error("SELECT * FROM pupl"),
+        "unknown table `pupl`, did you mean `pupil`?"
+    );
+    assert_eq!(error("SELECT * FROM course"), "unknown table `course`");
+
+    let schema = syn::parse_str::<Schema>("table! { pupil (idd) { id -> Int4, } }");
+    assert_eq!(
+        schema.unwrap_err().to_string(),
+        "unknown column `idd` in `pupil`, did you mean `id`?"
+    );
+}
+
+#[test]
+fn suggestions_allow_one_typo_per_three_characters() {
+    let candidates = || ["pupil", "teacher"].iter().map(|c| c.to_string());
+
+    assert_eq!(did_you_mean("pupl", candidates()).as_deref(), Some("pupil"));
+    assert_eq!(
+        did_you_mean("teachr", candidates()).as_deref(),
+        Some("teacher")
+    );
+    assert_eq!(did_you_mean("tchr", candidates()), None);
+    assert_eq!(did_you_mean("id", candidates()), None);
+}
+
+#[test]
+fn column_types_from_schema() {
+    assert_eq!(
+        columns("SELECT id, email FROM pupil"),
+        pairs(&[("id", "Int4"), ("email", "Nullable<Text>")])
+    );
+    assert_eq!(
+        columns("SELECT * FROM teacher"),
+        pairs(&[
+            ("teacher.id", "Int4"),
+            ("teacher.name", "Text"),
+            ("teacher.active", "Bool")
+        ])
+    );
+}
+
+#[test]
+fn params_from_insert_targets() {
+    assert_eq!(
+        params("INSERT INTO pupil (name, email, score) VALUES ($1, $2, $3)"),
+        ["Text", "Nullable<Text>", "Int4"]
+    );
+    assert_eq!(
+        params("INSERT INTO enrollment VALUES ($1, $2)"),
+        ["Int4", "Text"]
+    );
+}
+
+#[test]
+fn params_from_comparisons() {
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE name = $1 AND score > $2"),
+        ["Text", "Int4"]
+    );
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE teacher_id = $1 OR email = $2"),
+        ["Int4", "Text"]
+    );
+}
+
+#[test]
+fn params_from_update_assignments() {
+    assert_eq!(
+        params("UPDATE pupil SET name = $1, email = $2 WHERE id = $3"),
+        ["Text", "Nullable<Text>", "Int4"]
+    );
+    assert_eq!(
+        params("UPDATE pupil p SET score = $1 WHERE p.name = $2"),
+        ["Int4", "Text"]
+    );
+    assert_eq!(
+        columns("UPDATE pupil SET score = 0 WHERE id = $1 RETURNING id, score"),
+        pairs(&[("id", "Int4"), ("score", "Int4")])
+    );
+}
+
+#[test]
+fn update_errors() {
+    assert_eq!(
+        error("UPDATE pupil SET name = $1, name = $2 WHERE id = 1"),
+        "column `name` assigned more than once"
+    );
+    assert_eq!(
+        error("UPDATE pupil SET score = NULL WHERE id = 1"),
+        "column `score` is NOT NULL"
+    );
+    assert_eq!(
+        error("UPDATE pupil SET nme = $1 WHERE id = 1"),
+        "unknown column `nme` in `pupil`, did you mean `name`?"
+    );
+    assert_eq!(
+        error("UPDATE pupil SET name = $1 WHERE nme = $2"),
+        "unknown column `nme` in `pupil`, did you mean `name`?"
+    );
+}
+
+#[test]
+fn deny_key_update() {
+    assert_eq!(
+        params("UPDATE pupil SET id = $1 WHERE id = $2"),
+        ["Int4", "Int4"]
+    );
+    assert_eq!(
+        error(":deny_key_update UPDATE pupil SET id = $1 WHERE id = $2"),
+        "update of primary key column `id` is denied"
+    );
+    assert_eq!(
+        error(":deny_key_update UPDATE enrollment SET course = $1 WHERE pupil_id = $2"),
+        "update of primary key column `course` is denied"
+    );
+    assert!(syn::parse_str::<SqlStatement>(
+        "-- \"schema.rs\" Case :deny_key_updates SELECT id FROM pupil"
+    )
+    .unwrap_err()
+    .to_string()
+    .starts_with("unknown flag `deny_key_updates`, did you mean `deny_key_update`?"));
+}
+
+#[test]
+fn update_without_where_warns() {
+    assert_eq!(
+        warnings("UPDATE pupil SET score = 0"),
+        ["UPDATE without WHERE changes every row of `pupil`, add `WHERE TRUE` if intended"]
+    );
+    assert!(warnings("UPDATE pupil SET score = 0 WHERE TRUE").is_empty());
+}
+
+#[test]
+fn delete_statements() {
+    assert_eq!(
+        params("DELETE FROM pupil p WHERE p.teacher_id = $1 AND score < $2"),
+        ["Int4", "Int4"]
+    );
+    assert_eq!(
+        columns("DELETE FROM pupil WHERE id = $1 RETURNING name, email"),
+        pairs(&[("name", "Text"), ("email", "Nullable<Text>")])
+    );
+    assert_eq!(
+        error("DELETE FROM pupil WHERE nme = $1"),
+        "unknown column `nme` in `pupil`, did you mean `name`?"
+    );
+    assert!(warnings("DELETE FROM pupil").is_empty());
+}
+
+#[test]
+fn require_where() {
+    assert_eq!(
+        params(":require_where DELETE FROM pupil WHERE id = $1"),
+        ["Int4"]
+    );
+    assert_eq!(
+        error(":require_where DELETE FROM pupil"),
+        "DELETE from `pupil` requires a WHERE clause"
+    );
+    assert_eq!(
+        error(":require_where UPDATE pupil SET score = 0"),
+        "UPDATE of `pupil` requires a WHERE clause"
+    );
+}
+
+#[test]
+fn on_conflict() {
+    assert_eq!(
+        params(
+            "INSERT INTO pupil (id, name, score) VALUES ($1, $2, $3) \
+             ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, score = $4 \
+             WHERE pupil.score < excluded.score"
+        ),
+        ["Int4", "Text", "Int4", "Int4"]
+    );
+    assert_eq!(
+        params("INSERT INTO enrollment VALUES ($1, $2) ON CONFLICT (course, pupil_id) DO NOTHING"),
+        ["Int4", "Text"]
+    );
+    assert_eq!(
+        params("INSERT INTO pupil (name, score) VALUES ($1, 0) ON CONFLICT DO NOTHING"),
+        ["Text"]
+    );
+}
+
+#[test]
+fn on_conflict_errors() {
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1, 0) ON CONFLICT (email) DO NOTHING"),
+        "ON CONFLICT target must be the primary key (id) of `pupil`"
+    );
+    assert_eq!(
+        error("INSERT INTO enrollment VALUES ($1, $2) ON CONFLICT (pupil_id) DO NOTHING"),
+        "ON CONFLICT target must be the primary key (pupil_id, course) of `enrollment`"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1, 0) ON CONFLICT (idd) DO NOTHING"),
+        "unknown column `idd` in `pupil`, did you mean `id`?"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1, 0) ON CONFLICT DO UPDATE SET score = 0"),
+        "ON CONFLICT DO UPDATE requires a conflict target, e.g. `ON CONFLICT (id)`"
+    );
+}
+
+#[test]
+fn table_names_ignore_case() {
+    assert_eq!(
+        columns("SELECT Pupil.id FROM PUPIL"),
+        pairs(&[("id", "Int4")])
+    );
+    assert_eq!(params("UPDATE Pupil SET score = $1 WHERE id = 1"), ["Int4"]);
+
+    let schema = syn::parse_str::<Schema>("table! { pupil (ID) { id -> Int4, } }");
+    assert!(schema.is_ok());
+}
+
+#[test]
+fn insert_errors() {
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($2, $3)"),
+        "parameter $2 is used, but $1 is not"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1, $1)"),
+        "parameter $1 is used as both Text and Int4"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1)"),
+        "expected 2 values, found 1"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES (1, 2)"),
+        "cannot assign Int4 to column `name` of type Text"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1, NULL)"),
+        "column `score` is NOT NULL"
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (nme, score) VALUES ($1, $2)"),
+        "unknown column `nme` in `pupil`, did you mean `name`?"
+    );
+}
+
+#[test]
+fn insert_requires_not_null_columns() {
+    assert_eq!(
+        error("INSERT INTO pupil (name) VALUES ($1)"),
+        "missing value for NOT NULL column `score`"
+    );
+    // only a single integer primary key is assumed to be generated
+    assert_eq!(
+        error("INSERT INTO enrollment (course) VALUES ($1)"),
+        "missing value for NOT NULL column `pupil_id`"
+    );
+}
+
+#[test]
+fn returning_columns() {
+    assert_eq!(
+        columns("INSERT INTO pupil (name, score) VALUES ($1, 0) RETURNING id"),
+        pairs(&[("id", "Int4")])
+    );
+    assert_eq!(
+        error("INSERT INTO pupil (name, score) VALUES ($1, 0) RETURNING nme"),
+        "unknown column `nme` in `pupil`, did you mean `name`?"
+    );
+}
+
+#[test]
+fn rust_types() {
+    // the Rust type of the first parameter
+    let ty = |sql: &str| {
+        let mut params = Params::default();
+        statement(sql)
+            .walk_projection(&schema(), &mut params, &mut |_| {})
+            .unwrap();
+        let (span, ty) = &params.finish().unwrap()[0];
+        column_type_to_param_ty(ty, *span).unwrap().to_string()
+    };
+
+    assert_eq!(
+        ty("UPDATE pupil SET score = 0 WHERE name = $1"),
+        quote! { &str }.to_string()
+    );
+    assert_eq!(
+        ty("SELECT teacher_id FROM pupil GROUP BY teacher_id HAVING avg(score) > $1"),
+        quote! { ::rust_decimal::Decimal }.to_string()
+    );
+    assert_eq!(
+        ty("SELECT id FROM pupil WHERE name = $1::varchar"),
+        quote! { &str }.to_string()
+    );
+    assert_eq!(
+        ty("INSERT INTO pupil (name, score) VALUES ($1, 0)"),
+        quote! { &str }.to_string()
+    );
+    assert_eq!(
+        ty(r#"INSERT INTO pupil (email, name, score) VALUES ($1, "", 0)"#),
+        quote! { ::std::option::Option<&str> }.to_string()
+    );
+}
+
+#[test]
+fn unsupported_types_are_errors() {
+    let mut projections = Vec::new();
+    statement("SELECT created FROM pupil")
+        .walk_projection(&schema(), &mut Params::default(), &mut |p| {
+            projections.push(p.clone())
+        })
+        .unwrap();
+
+    assert_eq!(
+        sql_type_to_ty(&projections[0].ty.ty, projections[0].span)
+            .unwrap_err()
+            .to_string(),
+        "the SQL type `Timestamp` has no Rust type"
+    );
+}
+
+#[test]
+fn order_by_and_limits() {
+    assert_eq!(
+        params("SELECT id, score AS points FROM pupil ORDER BY points DESC, 1 LIMIT $1 OFFSET $2"),
+        ["Int8", "Int8"]
+    );
+    assert_eq!(
+        params("SELECT name FROM pupil ORDER BY score NULLS LAST FETCH FIRST $1 ROWS ONLY"),
+        ["Int8"]
+    );
+    assert!(params("SELECT name FROM pupil LIMIT ALL OFFSET 10 ROWS").is_empty());
+
+    let sql = statement("SELECT name FROM pupil ORDER BY name LIMIT 10").to_string();
+    assert!(sql.contains("ORDER BY"), "{}", sql);
+    assert!(sql.contains("LIMIT 10"), "{}", sql);
+}
+
+#[test]
+fn order_by_and_limit_errors() {
+    assert_eq!(
+        error("SELECT id, name FROM pupil ORDER BY 3"),
+        "ORDER BY position 3 is not in select list"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil ORDER BY nme"),
+        "unknown column `nme` in `pupil`, did you mean `name`?"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil LIMIT TRUE"),
+        "expected a row count, found Bool"
+    );
+}
+
+#[test]
+fn aggregate_types() {
+    assert_eq!(
+        columns(
+            "SELECT count(*) AS n, count(email) AS emails, max(score) AS best, \
+             sum(score) AS total, avg(score) AS mean, string_agg(name, \", \") AS names \
+             FROM pupil"
+        ),
+        pairs(&[
+            ("n", "Int8"),
+            ("emails", "Int8"),
+            ("best", "Nullable<Int4>"),
+            ("total", "Nullable<Int8>"),
+            ("mean", "Nullable<Numeric>"),
+            ("names", "Nullable<Text>")
+        ])
+    );
+    assert_eq!(
+        columns("SELECT teacher_id, bool_or(score > 5) AS passed FROM pupil GROUP BY teacher_id"),
+        pairs(&[
+            ("teacher_id", "Nullable<Int4>"),
+            ("passed", "Nullable<Bool>")
+        ])
+    );
+    assert_eq!(
+        params(
+            "SELECT teacher_id, count(*) AS n FROM pupil WHERE score > $1 \
+             GROUP BY 1 HAVING count(*) > $2"
+        ),
+        ["Int4", "Int8"]
+    );
+}
+
+#[test]
+fn grouping_errors() {
+    assert_eq!(
+        error("SELECT name, count(*) AS n FROM pupil"),
+        "column `pupil.name` must appear in the GROUP BY clause or be used in an aggregate function"
+    );
+    assert_eq!(
+        error("SELECT teacher_id FROM pupil GROUP BY teacher_id HAVING score > 1"),
+        "column `pupil.score` must appear in the GROUP BY clause or be used in an aggregate function"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil WHERE count(*) > 1"),
+        "aggregate functions are not allowed in WHERE"
+    );
+    assert_eq!(
+        error("SELECT max(count(*)) AS n FROM pupil"),
+        "aggregate function calls cannot be nested"
+    );
+    assert_eq!(
+        error("SELECT teacher_id FROM pupil GROUP BY 2"),
+        "GROUP BY position 2 is not in select list"
+    );
+    assert_eq!(
+        error("SELECT sum(*) AS n FROM pupil"),
+        "`*` is only allowed in `count(*)`, not in `sum`"
+    );
+    assert_eq!(
+        error("SELECT cont(*) AS n FROM pupil"),
+        "unknown function `cont`, did you mean `count`?"
+    );
+}
+
+#[test]
+fn expression_types() {
+    assert_eq!(
+        columns(
+            "SELECT score * 2 AS double_score, score / 2.5 AS ratio, -score AS negative, \
+             name || email AS contact, lower(name), round(avg(score), 1) AS mean \
+             FROM pupil GROUP BY score, name, email"
+        ),
+        pairs(&[
+            ("double_score", "Int4"),
+            ("ratio", "Numeric"),
+            ("negative", "Int4"),
+            ("contact", "Nullable<Text>"),
+            ("lower", "Text"),
+            ("mean", "Nullable<Numeric>")
+        ])
+    );
+    assert_eq!(
+        columns("SELECT (id + teacher_id) AS sum, length(name) FROM pupil"),
+        pairs(&[("sum", "Nullable<Int4>"), ("length", "Int4")])
+    );
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE score * $1 > $2 AND upper(name) = $3"),
+        ["Int4", "Int4", "Text"]
+    );
+}
+
+#[test]
+fn expression_errors() {
+    assert_eq!(
+        error("SELECT name * 2 AS n FROM pupil"),
+        "operator does not exist: Text * Int4"
+    );
+    assert_eq!(
+        error("SELECT lower(score) FROM pupil"),
+        "function `lower(Int4)` does not exist"
+    );
+    assert_eq!(
+        error("SELECT lowr(name) FROM pupil"),
+        "unknown function `lowr`, did you mean `lower`?"
+    );
+}
+
+#[test]
+fn casts() {
+    assert_eq!(
+        columns(
+            "SELECT CAST(score AS bigint) AS big, teacher_id::text AS teacher, \
+             score::double precision AS ratio, name::varchar(20) AS short FROM pupil"
+        ),
+        pairs(&[
+            ("big", "Int8"),
+            ("teacher", "Nullable<Text>"),
+            ("ratio", "Float8"),
+            ("short", "Varchar")
+        ])
+    );
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE $1::text IS NULL OR name = $1"),
+        ["Text"]
+    );
+    assert_eq!(
+        error("SELECT score::intger AS n FROM pupil"),
+        "unknown type `intger`, did you mean `integer`?"
+    );
+}
+
+#[test]
+fn conditional_nullability() {
+    assert_eq!(
+        columns(
+            "SELECT coalesce(email, \"none\") AS contact, coalesce(email, teacher_id::text) AS any, \
+             nullif(score, 0) AS nonzero, greatest(score, teacher_id) AS high, \
+             CASE WHEN score > 5 THEN name ELSE \"failed\" END AS result, \
+             CASE score WHEN 1 THEN name END AS first FROM pupil"
+        ),
+        pairs(&[
+            ("contact", "Text"),
+            ("any", "Nullable<Text>"),
+            ("nonzero", "Nullable<Int4>"),
+            // NULL arguments are ignored unless all are NULL
+            ("high", "Int4"),
+            ("result", "Text"),
+            ("first", "Nullable<Text>")
+        ])
+    );
+    assert_eq!(
+        params("SELECT coalesce(email, $1) AS contact FROM pupil WHERE CASE WHEN $2 THEN TRUE END"),
+        ["Text", "Bool"]
+    );
+    assert_eq!(
+        error("SELECT coalesce(name, score) AS x FROM pupil"),
+        "coalesce types Text and Int4 cannot be matched"
+    );
+    assert_eq!(
+        error("SELECT CASE WHEN TRUE THEN 1 ELSE name END AS x FROM pupil"),
+        "CASE types Int4 and Text cannot be matched"
+    );
+}
+
+#[test]
+fn quoted_aliases_override_nullability() {
+    assert_eq!(
+        columns("SELECT email AS \"email!\", score AS \"score?\", name AS \"name\" FROM pupil"),
+        pairs(&[
+            ("email", "Text"),
+            ("score", "Nullable<Int4>"),
+            ("name", "Text")
+        ])
+    );
+    assert_eq!(
+        error("SELECT name AS \"full name\" FROM pupil"),
+        "`full name` is no valid field name"
+    );
+
+    // Postgres gets the bare name, the override is only for the Rust side
+    let sql = statement("SELECT email AS \"email!\", score \"score?\" FROM pupil").to_string();
+    assert!(sql.contains("AS \"email\""), "{}", sql);
+    assert!(sql.contains("score \"score\""), "{}", sql);
+    assert!(!sql.contains('!') && !sql.contains('?'), "{}", sql);
+}
+
+#[test]
+fn set_operations() {
+    assert_eq!(
+        columns(
+            "SELECT id, email AS contact FROM pupil \
+             UNION SELECT count(*), name FROM teacher GROUP BY name ORDER BY contact"
+        ),
+        pairs(&[("id", "Int8"), ("contact", "Nullable<Text>")])
+    );
+    assert_eq!(
+        columns(
+            "SELECT email FROM pupil INTERSECT ALL SELECT name FROM teacher \
+             EXCEPT SELECT name FROM pupil"
+        ),
+        pairs(&[("email", "Text")])
+    );
+    assert_eq!(
+        columns("SELECT email FROM pupil EXCEPT SELECT name FROM teacher"),
+        pairs(&[("email", "Nullable<Text>")])
+    );
+    assert_eq!(
+        params("(SELECT id FROM pupil WHERE score > $1) UNION SELECT id FROM teacher ORDER BY 1 LIMIT $2"),
+        ["Int4", "Int8"]
+    );
+}
+
+#[test]
+fn set_operation_errors() {
+    assert_eq!(
+        error("SELECT id, name FROM pupil UNION SELECT id FROM teacher"),
+        "each UNION query must have the same number of columns"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil EXCEPT SELECT name FROM teacher"),
+        "EXCEPT types Int4 and Text cannot be matched"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil UNION SELECT id FROM teacher ORDER BY 2"),
+        "ORDER BY position 2 is not in select list"
+    );
+    // only the output columns can be sorted by
+    assert_eq!(
+        error("SELECT id FROM pupil UNION SELECT id FROM teacher ORDER BY score"),
+        "unknown column `score`"
+    );
+}
+
+#[test]
+fn derived_tables() {
+    assert_eq!(
+        columns(
+            "SELECT t.teacher_id, n FROM \
+             (SELECT teacher_id, count(*) AS n FROM pupil WHERE score > $1 GROUP BY teacher_id) AS t \
+             WHERE n > $2"
+        ),
+        pairs(&[("teacher_id", "Nullable<Int4>"), ("n", "Int8")])
+    );
+    assert_eq!(
+        params(
+            "SELECT t.teacher_id, n FROM \
+             (SELECT teacher_id, count(*) AS n FROM pupil WHERE score > $1 GROUP BY teacher_id) AS t \
+             WHERE n > $2"
+        ),
+        ["Int4", "Int8"]
+    );
+    assert_eq!(
+        columns("SELECT a, b FROM (SELECT id, email FROM pupil) p(a, b)"),
+        pairs(&[("a", "Int4"), ("b", "Nullable<Text>")])
+    );
+    // columns without an alias keep their name
+    assert_eq!(
+        columns("SELECT * FROM (SELECT id, name FROM teacher) AS t(n)"),
+        pairs(&[("t.n", "Int4"), ("t.name", "Text")])
+    );
+}
+
+#[test]
+fn derived_table_errors() {
+    assert_eq!(
+        parse_error("SELECT id FROM (SELECT id FROM pupil)"),
+        "subquery in FROM must have an alias, add `AS name`"
+    );
+    assert_eq!(
+        error("SELECT a FROM (SELECT id FROM pupil) AS t(a, b)"),
+        "`t` has 1 columns available but 2 columns specified"
+    );
+    assert_eq!(
+        error("SELECT * FROM (SELECT score + 1 FROM pupil) AS t"),
+        "expression in a subquery needs a name to be referenced, add `AS name`"
+    );
+    assert_eq!(
+        error("SELECT * FROM (SELECT id, name AS id FROM pupil) AS t"),
+        "column `id` specified more than once"
+    );
+    assert_eq!(
+        error("SELECT email FROM (SELECT id FROM pupil) AS t"),
+        "unknown column `email` in `t`"
+    );
+}
+
+#[test]
+fn subqueries() {
+    assert_eq!(
+        columns(
+            "SELECT t.name, (SELECT max(score) FROM pupil p WHERE p.teacher_id = t.id) AS best, \
+             (SELECT count(*) FROM pupil WHERE teacher_id = t.id), \
+             EXISTS (SELECT id FROM pupil WHERE teacher_id = t.id) AS busy FROM teacher t"
+        ),
+        pairs(&[
+            ("name", "Text"),
+            ("best", "Nullable<Int4>"),
+            // a scalar subquery without rows is NULL
+            ("count", "Nullable<Int8>"),
+            ("busy", "Bool")
+        ])
+    );
+    assert_eq!(
+        columns(
+            "SELECT id, teacher_id IN (SELECT id FROM teacher WHERE active) AS active FROM pupil \
+             WHERE id NOT IN (SELECT pupil_id FROM enrollment WHERE course = $1)"
+        ),
+        pairs(&[("id", "Int4"), ("active", "Nullable<Bool>")])
+    );
+    assert_eq!(
+        params(
+            "SELECT id FROM pupil WHERE $1 IN (SELECT course FROM enrollment WHERE pupil_id = id) \
+             AND score > (SELECT avg(score) FROM pupil WHERE teacher_id = $2)"
+        ),
+        ["Text", "Int4"]
+    );
+    // the innermost table with the column wins
+    assert_eq!(
+        columns("SELECT (SELECT name FROM teacher WHERE id = 1) AS n FROM pupil"),
+        pairs(&[("n", "Nullable<Text>")])
+    );
+}
+
+#[test]
+fn subquery_errors() {
+    assert_eq!(
+        error("SELECT (SELECT id, name FROM teacher) AS t FROM pupil"),
+        "subquery must return only one column"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil WHERE name IN (SELECT id FROM teacher)"),
+        "cannot compare Text with Int4"
+    );
+    assert_eq!(
+        parse_error("SELECT id FROM pupil WHERE id IN (1, 2)"),
+        "expected a subquery, e.g. `IN (SELECT ...)`"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil WHERE EXISTS (SELECT 1 FROM teacher WHERE idd = id)"),
+        "unknown column `idd`, did you mean `id`?"
+    );
+}
+
+#[test]
+fn common_table_expressions() {
+    assert_eq!(
+        columns(
+            "WITH good AS (SELECT id, teacher_id FROM pupil WHERE score > $1), \
+             counts(teacher, n) AS (SELECT teacher_id, count(*) FROM good GROUP BY teacher_id) \
+             SELECT teacher, n FROM counts"
+        ),
+        pairs(&[("teacher", "Nullable<Int4>"), ("n", "Int8")])
+    );
+    // CTEs are visible in subqueries
+    assert_eq!(
+        columns(
+            "WITH best AS (SELECT max(score) AS score FROM pupil) \
+             SELECT name FROM pupil WHERE score = (SELECT score FROM best)"
+        ),
+        pairs(&[("name", "Text")])
+    );
+    assert_eq!(
+        params(
+            "WITH old AS (SELECT id FROM pupil WHERE score < $1) \
+             DELETE FROM pupil WHERE id IN (SELECT id FROM old) RETURNING name"
+        ),
+        ["Int4"]
+    );
+    assert_eq!(
+        params(
+            "WITH t AS (SELECT id FROM teacher WHERE name = $1) \
+             UPDATE pupil SET teacher_id = (SELECT id FROM t) WHERE id = $2"
+        ),
+        ["Text", "Int4"]
+    );
+}
+
+#[test]
+fn recursive_common_table_expressions() {
+    // the recursive term makes `teacher_id` nullable, although the first one isn't
+    assert_eq!(
+        columns(
+            "WITH RECURSIVE chain(id, up) AS ( \
+             SELECT id, 1 FROM pupil WHERE id = $1 \
+             UNION ALL SELECT p.id, p.teacher_id FROM pupil p, chain c WHERE p.id = c.up) \
+             SELECT id, up FROM chain"
+        ),
+        pairs(&[("id", "Int4"), ("up", "Nullable<Int4>")])
+    );
+    assert_eq!(
+        error(
+            "WITH RECURSIVE chain(id, up) AS ( \
+             SELECT id, 1 FROM pupil \
+             UNION ALL SELECT p.id, p.name FROM pupil p, chain c WHERE p.id = c.up) \
+             SELECT id FROM chain"
+        ),
+        "UNION types Int4 and Text cannot be matched"
+    );
+    assert_eq!(
+        error(
+            "WITH RECURSIVE chain(id, n) AS ( \
+             SELECT id, 1 FROM pupil \
+             UNION ALL SELECT c.id, c.n + 1::int8 FROM chain c) \
+             SELECT id FROM chain"
+        ),
+        "recursive query `chain` column 2 has type Int4 in non-recursive term but type Int8 overall"
+    );
+}
+
+#[test]
+fn common_table_expression_errors() {
+    assert_eq!(
+        error("WITH t AS (SELECT id FROM pupil), T AS (SELECT id FROM teacher) SELECT id FROM t"),
+        "WITH query name `T` specified more than once"
+    );
+    assert_eq!(
+        error("WITH t(a, b) AS (SELECT id FROM pupil) SELECT a FROM t"),
+        "`t` has 1 columns available but 2 columns specified"
+    );
+    // a CTE is only visible after its definition without RECURSIVE
+    assert_eq!(
+        error("WITH t AS (SELECT id FROM t) SELECT id FROM t"),
+        "unknown table `t`"
+    );
+}
+
+#[test]
+fn window_functions() {
+    assert_eq!(
+        columns(
+            "SELECT id, row_number() OVER (PARTITION BY teacher_id ORDER BY score DESC) AS rank, \
+             lag(score) OVER w AS previous, sum(score) OVER w AS total, \
+             avg(score) OVER (w ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) AS moving \
+             FROM pupil WINDOW w AS (ORDER BY created)"
+        ),
+        pairs(&[
+            ("id", "Int4"),
+            ("rank", "Int8"),
+            ("previous", "Nullable<Int4>"),
+            ("total", "Nullable<Int8>"),
+            ("moving", "Nullable<Numeric>")
+        ])
+    );
+    assert_eq!(
+        params("SELECT ntile($1) OVER (ORDER BY score) AS bucket FROM pupil"),
+        ["Int4"]
+    );
+}
+
+#[test]
+fn window_function_errors() {
+    assert_eq!(
+        error("SELECT row_number() AS n FROM pupil"),
+        "window function `row_number` requires an OVER clause"
+    );
+    assert_eq!(
+        error("SELECT lower(name) OVER () AS n FROM pupil"),
+        "OVER specified, but `lower` is not a window function nor an aggregate function"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil WHERE rank() OVER () > 1"),
+        "window functions are not allowed in WHERE"
+    );
+    assert_eq!(
+        error("SELECT rank() OVER w AS r FROM pupil"),
+        "window `w` does not exist"
+    );
+    assert_eq!(
+        error("SELECT rank() OVER w AS r FROM pupil WINDOW w AS (), w AS (ORDER BY id)"),
+        "window `w` is already defined"
+    );
+    assert_eq!(
+        error("SELECT sum(rank() OVER ()) OVER () AS r FROM pupil"),
+        "window function calls cannot be nested"
+    );
+    assert_eq!(
+        error(
+            "SELECT sum(score) OVER (ROWS BETWEEN UNBOUNDED FOLLOWING AND CURRENT ROW) AS s FROM pupil"
+        ),
+        "frame start cannot be UNBOUNDED FOLLOWING"
+    );
+}
+
+#[test]
+fn distinct_on() {
+    assert_eq!(
+        columns(
+            "SELECT DISTINCT ON (teacher_id) teacher_id, name FROM pupil \
+             ORDER BY teacher_id, score DESC"
+        ),
+        pairs(&[("teacher_id", "Nullable<Int4>"), ("name", "Text")])
+    );
+    // output names and positions refer to the selected expressions
+    assert_eq!(
+        columns(
+            "SELECT DISTINCT ON (t, 2) teacher_id AS t, lower(name) AS n FROM pupil \
+             ORDER BY 2, teacher_id"
+        ),
+        pairs(&[("t", "Nullable<Int4>"), ("n", "Text")])
+    );
+    assert_eq!(
+        columns(
+            "SELECT DISTINCT ON (teacher_id) teacher_id, count(*) AS n FROM pupil \
+             GROUP BY teacher_id"
+        ),
+        pairs(&[("teacher_id", "Nullable<Int4>"), ("n", "Int8")])
+    );
+}
+
+#[test]
+fn distinct_on_errors() {
+    assert_eq!(
+        error("SELECT DISTINCT ON (teacher_id) name FROM pupil ORDER BY score"),
+        "SELECT DISTINCT ON expressions must match initial ORDER BY expressions"
+    );
+    assert_eq!(
+        error("SELECT DISTINCT ON (3) id, name FROM pupil"),
+        "DISTINCT ON position 3 is not in select list"
+    );
+    assert_eq!(
+        error("SELECT DISTINCT ON (name) teacher_id, count(*) AS n FROM pupil GROUP BY teacher_id"),
+        "column `pupil.name` must appear in the GROUP BY clause or be used in an aggregate function"
+    );
+}
+
+#[test]
+fn joins() {
+    assert_eq!(
+        columns(
+            "SELECT p.name, t.name AS teacher FROM pupil p \
+             LEFT JOIN teacher t ON t.id = p.teacher_id AND t.active = $1"
+        ),
+        pairs(&[("name", "Text"), ("teacher", "Nullable<Text>")])
+    );
+    assert_eq!(
+        columns("SELECT * FROM pupil p JOIN enrollment e ON e.pupil_id = p.id"),
+        pairs(&[
+            ("p.id", "Int4"),
+            ("p.name", "Text"),
+            ("p.email", "Nullable<Text>"),
+            ("p.teacher_id", "Nullable<Int4>"),
+            ("p.score", "Int4"),
+            ("p.created", "Nullable<Timestamp>"),
+            ("e.pupil_id", "Int4"),
+            ("e.course", "Text")
+        ])
+    );
+    // the merged column comes first and has no table name
+    assert_eq!(
+        columns("SELECT * FROM teacher t FULL JOIN pupil p USING (id, name)"),
+        pairs(&[
+            ("id", "Int4"),
+            ("name", "Text"),
+            ("t.active", "Nullable<Bool>"),
+            ("p.email", "Nullable<Text>"),
+            ("p.teacher_id", "Nullable<Int4>"),
+            ("p.score", "Nullable<Int4>"),
+            ("p.created", "Nullable<Timestamp>")
+        ])
+    );
+    assert_eq!(
+        columns("SELECT id, t.name, p.name AS pupil FROM teacher t NATURAL JOIN pupil p"),
+        pairs(&[("id", "Int4"), ("name", "Text"), ("pupil", "Text")])
+    );
+    assert_eq!(
+        columns("SELECT t.id, e.course FROM teacher t CROSS JOIN enrollment e"),
+        pairs(&[("id", "Int4"), ("course", "Text")])
+    );
+}
+
+#[test]
+fn join_errors() {
+    assert_eq!(
+        error("SELECT id FROM pupil JOIN teacher ON teacher.id = pupil.teacher_id"),
+        "column reference `id` is ambiguous"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil p JOIN teacher t ON count(*) > 1"),
+        "aggregate functions are not allowed in JOIN conditions"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil JOIN teacher USING (id, id)"),
+        "column `id` appears more than once in USING clause"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil JOIN teacher USING (score)"),
+        "column `score` specified in USING clause does not exist in right table"
+    );
+    assert_eq!(
+        error("SELECT p.id FROM pupil p CROSS JOIN teacher t NATURAL JOIN teacher"),
+        "common column name `id` appears more than once in left table"
+    );
+    assert_eq!(
+        error("SELECT id FROM teacher t JOIN pupil p USING (name, id) WHERE t.id = p.name"),
+        "cannot compare Int4 with Text"
+    );
+}
+
+#[test]
+fn duplicate_output_names() {
+    let fields_error = |sql| {
+        let mut projections = Vec::new();
+        statement(sql)
+            .walk_projection(&schema(), &mut Params::default(), &mut |p| {
+                projections.push(p.clone())
+            })
+            .unwrap();
+
+        projections_to_fields(&projections).unwrap_err().to_string()
+    };
+
+    assert_eq!(
+        fields_error("SELECT p.name, t.name FROM pupil p JOIN teacher t ON t.id = p.teacher_id"),
+        "column `name` is selected more than once, add an alias with `AS name`"
+    );
+    // `*` names columns `table.column`, which can still collide
+    assert_eq!(
+        fields_error("SELECT * FROM teacher CROSS JOIN teacher"),
+        "column `teacher_id` is selected more than once, add an alias with `AS name`"
+    );
+    assert_eq!(
+        fields_error(
+            "SELECT * FROM teacher CROSS JOIN pupil p \
+             NATURAL JOIN (SELECT teacher_id FROM pupil) AS x"
+        ),
+        "column `teacher_id` is selected more than once, add an alias with `AS name`"
+    );
+}
+
+#[test]
+fn qualified_stars() {
+    assert_eq!(
+        columns("SELECT t.*, p.name AS pupil FROM pupil p JOIN teacher t ON p.teacher_id = t.id"),
+        pairs(&[
+            ("t.id", "Int4"),
+            ("t.name", "Text"),
+            ("t.active", "Bool"),
+            ("pupil", "Text")
+        ])
+    );
+    // the optional side of the join stays optional
+    assert_eq!(
+        columns("SELECT e.* FROM pupil p LEFT JOIN enrollment e ON e.pupil_id = p.id"),
+        pairs(&[
+            ("e.pupil_id", "Nullable<Int4>"),
+            ("e.course", "Nullable<Text>")
+        ])
+    );
+    assert_eq!(
+        columns("SELECT teacher.*, count(*) AS n FROM teacher GROUP BY teacher.id"),
+        pairs(&[
+            ("teacher.id", "Int4"),
+            ("teacher.name", "Text"),
+            ("teacher.active", "Bool"),
+            ("n", "Int8")
+        ])
+    );
+}
+
+#[test]
+fn star_errors() {
+    assert_eq!(error("SELECT *"), "`*` requires a FROM clause");
+    assert_eq!(
+        error("SELECT x.* FROM pupil p"),
+        "unknown table `x`, did you mean `p`?"
+    );
+    assert_eq!(
+        error("SELECT p.*, count(*) AS n FROM pupil p GROUP BY p.name"),
+        "column `p.id` must appear in the GROUP BY clause or be used in an aggregate function"
+    );
+}
+
+#[test]
+fn lateral_subqueries() {
+    assert_eq!(
+        columns(
+            "SELECT t.name, best.score FROM teacher t, \
+             LATERAL (SELECT score FROM pupil p WHERE p.teacher_id = t.id ORDER BY score DESC LIMIT 3) best"
+        ),
+        pairs(&[("name", "Text"), ("score", "Int4")])
+    );
+    assert_eq!(
+        columns(
+            "SELECT p.id, s.n FROM pupil p \
+             LEFT JOIN LATERAL (SELECT count(*) AS n FROM enrollment e WHERE e.pupil_id = p.id) s ON TRUE"
+        ),
+        pairs(&[("id", "Int4"), ("n", "Nullable<Int8>")])
+    );
+    // without LATERAL the subquery cannot see the tables before it
+    assert_eq!(
+        error("SELECT best.score FROM teacher t, (SELECT score FROM pupil p WHERE p.teacher_id = t.id) best"),
+        "unknown table `t`, did you mean `p`?"
+    );
+    assert_eq!(
+        parse_error("SELECT id FROM pupil, LATERAL teacher"),
+        "expected a subquery or function after LATERAL"
+    );
+}
+
+#[test]
+fn set_returning_functions() {
+    assert_eq!(
+        columns("SELECT p.name FROM unnest($1::int[]) AS id JOIN pupil p USING (id)"),
+        pairs(&[("name", "Text")])
+    );
+    assert_eq!(
+        columns("SELECT n, p.id FROM pupil p, generate_series(1, p.score) AS n"),
+        pairs(&[("n", "Int4"), ("id", "Int4")])
+    );
+    assert_eq!(
+        columns("SELECT value FROM jsonb_array_elements($1::jsonb) AS e"),
+        pairs(&[("value", "Jsonb")])
+    );
+    assert_eq!(
+        columns("SELECT x FROM generate_series(1, 3) AS t(x)"),
+        pairs(&[("x", "Int4")])
+    );
+
+    let mut params = Params::default();
+    statement("SELECT id FROM unnest($1::text[]) AS id")
+        .walk_projection(&schema(), &mut params, &mut |_| {})
+        .unwrap();
+    let (span, ty) = &params.finish().unwrap()[0];
+    assert_eq!(
+        column_type_to_param_ty(ty, *span).unwrap().to_string(),
+        quote! { &[&str] }.to_string()
+    );
+}
+
+#[test]
+fn set_returning_function_errors() {
+    assert_eq!(
+        error("SELECT id FROM unnest($1) AS id"),
+        "cannot infer the array type, add a cast like `$1::int[]`"
+    );
+    assert_eq!(
+        error("SELECT id FROM generate_serie(1, 2) AS id"),
+        "unknown set-returning function `generate_serie`, did you mean `generate_series`?"
+    );
+    assert_eq!(
+        error("SELECT id FROM generate_series(1, \"x\"::text) AS id"),
+        "function `generate_series(Int4, Text)` does not exist"
+    );
+}
+
+#[test]
+fn array_comparisons() {
+    assert_eq!(
+        params("SELECT name FROM pupil WHERE id = ANY($1) AND name <> ALL($2)"),
+        ["Array<Int4>", "Array<Text>"]
+    );
+    assert_eq!(
+        params("SELECT name FROM pupil WHERE id IN (SELECT unnest($1))"),
+        ["Array<Int4>"]
+    );
+    // the array types the left side
+    assert_eq!(
+        params("SELECT name FROM pupil WHERE $1 = ANY($2::int[])"),
+        ["Int4", "Array<Int4>"]
+    );
+    assert_eq!(
+        columns("SELECT email = ANY($1) AS listed FROM pupil"),
+        pairs(&[("listed", "Nullable<Bool>")])
+    );
+
+    let mut params = Params::default();
+    statement("SELECT name FROM pupil WHERE name = ANY($1)")
+        .walk_projection(&schema(), &mut params, &mut |_| {})
+        .unwrap();
+    let (span, ty) = &params.finish().unwrap()[0];
+    assert_eq!(
+        column_type_to_param_ty(ty, *span).unwrap().to_string(),
+        quote! { &[&str] }.to_string()
+    );
+}
+
+#[test]
+fn array_comparison_errors() {
+    assert_eq!(
+        error("SELECT name FROM pupil WHERE id = ANY($1::text[])"),
+        "cannot compare Int4 with Text"
+    );
+    assert_eq!(
+        error("SELECT name FROM pupil WHERE id = ANY(score)"),
+        "`ANY` and `ALL` require an array, found Int4"
+    );
+    assert_eq!(
+        parse_error("SELECT name FROM pupil WHERE id = ANY(SELECT id FROM teacher)"),
+        "`ANY` and `ALL` take an array, use `IN (SELECT ...)` for a subquery"
+    );
+}
+
+#[test]
+fn json_operators() {
+    // a missing key is `NULL`
+    assert_eq!(
+        columns(r#"SELECT data ->> "theme" AS theme, data -> "size" AS size FROM setting"#),
+        pairs(&[("theme", "Nullable<Text>"), ("size", "Nullable<Jsonb>")])
+    );
+    assert_eq!(
+        columns(r#"SELECT data #>> $1 AS theme, extra -> 0 AS first FROM setting"#),
+        pairs(&[("theme", "Nullable<Text>"), ("first", "Nullable<Json>")])
+    );
+    assert_eq!(
+        params(r#"SELECT id FROM setting WHERE data @> $1 AND data ? $2 AND data ?| $3"#),
+        ["Jsonb", "Text", "Array<Text>"]
+    );
+    assert_eq!(
+        columns(r#"SELECT data ? "theme" AS themed FROM setting"#),
+        pairs(&[("themed", "Bool")])
+    );
+
+    let projections = |sql| {
+        let mut projections = Vec::new();
+        statement(sql)
+            .walk_projection(&schema(), &mut Params::default(), &mut |p| {
+                projections.push(p.clone())
+            })
+            .unwrap();
+        projections
+    };
+    let (fields, _) = projections_to_fields(&projections(
+        r#"SELECT data AS "data: crate::Settings", extra AS "extra: Vec<i32>" FROM setting"#,
+    ))
+    .unwrap();
+    assert_eq!(
+        fields[0].to_string(),
+        quote! { pub data: crate::Settings }.to_string()
+    );
+    assert_eq!(
+        fields[1].to_string(),
+        quote! { pub extra: ::std::option::Option<Vec<i32> > }.to_string()
+    );
+
+    // Postgres only sees the bare name
+    assert!(
+        statement(r#"SELECT data AS "data: crate::Settings" FROM setting"#)
+            .to_string()
+            .contains(r#"data AS "data""#)
+    );
+}
+
+#[test]
+fn json_errors() {
+    assert_eq!(
+        error(r#"SELECT name AS "name: crate::Name" FROM pupil"#),
+        "only json columns can be deserialized into a type, found Text"
+    );
+    assert_eq!(
+        error(r#"SELECT data AS "data: x y" FROM setting"#),
+        "`x y` is no valid type"
+    );
+    assert_eq!(
+        error(r#"SELECT name ->> "k" AS k FROM pupil"#),
+        "operator does not exist: Text ->> Text"
+    );
+    assert_eq!(
+        error("SELECT id FROM setting WHERE extra @> extra"),
+        "operator does not exist: Json @> Json"
+    );
+}
+
+#[test]
+fn pattern_matching() {
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE name LIKE $1 OR email NOT ILIKE $2"),
+        ["Text", "Text"]
+    );
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE name SIMILAR TO $1 AND name !~* $2"),
+        ["Text", "Text"]
+    );
+    assert_eq!(
+        columns("SELECT email ~ $1 AS matches FROM pupil"),
+        pairs(&[("matches", "Nullable<Bool>")])
+    );
+    assert_eq!(
+        params("SELECT id FROM pupil WHERE to_tsvector(name) @@ plainto_tsquery($1)"),
+        ["Text"]
+    );
+    assert_eq!(
+        columns("SELECT ts_rank(to_tsvector(name), to_tsquery($1)) AS rank FROM pupil"),
+        pairs(&[("rank", "Float4")])
+    );
+}
+
+#[test]
+fn pattern_matching_errors() {
+    assert_eq!(
+        error("SELECT id FROM pupil WHERE score LIKE $1"),
+        "operator does not exist: Int4 LIKE Text"
+    );
+    assert_eq!(
+        error("SELECT id FROM pupil WHERE to_tsvector(name) @@ score"),
+        "operator does not exist: TsVector @@ Int4"
+    );
+}
+
+#[test]
+fn concatenated_patterns_warn() {
+    assert_eq!(
+        warnings(r#"SELECT id FROM pupil WHERE name LIKE "%" || $1 || "%""#),
+        ["parameter $1 is concatenated into a LIKE pattern unescaped, so `%` and `_` in it are wildcards, escape them with `replace`"]
+    );
+    assert_eq!(
+        warnings(r#"SELECT id FROM pupil WHERE name NOT ILIKE ($1::text || "%")"#).len(),
+        1
+    );
+    // escaped, a plain parameter or a regular expression
+    assert!(warnings(
+        r#"SELECT id FROM pupil WHERE name LIKE "%" || replace($1, "%", "\\%") || "%""#
+    )
+    .is_empty());
+    assert!(warnings("SELECT id FROM pupil WHERE name LIKE $1").is_empty());
+    assert!(warnings(r#"SELECT id FROM pupil WHERE name ~ ("^" || $1)"#).is_empty());
+}
+
+fn cardinality(sql: &str) -> syn::Result<Cardinality> {
+    statement(sql).cardinality(&schema())
+}
+
+#[test]
+fn cardinality_flags() {
+    assert_eq!(
+        cardinality("SELECT id FROM pupil WHERE id = $1").unwrap(),
+        Cardinality::Many
+    );
+    assert_eq!(
+        cardinality(":one SELECT count(*) AS n FROM pupil").unwrap(),
+        Cardinality::One
+    );
+    assert_eq!(
+        cardinality(":opt SELECT id FROM pupil WHERE name = $1").unwrap(),
+        Cardinality::Optional
+    );
+}
+
+#[test]
+fn inferred_cardinality() {
+    // each primary key column is fixed
+    assert_eq!(
+        cardinality(":infer SELECT name FROM pupil p WHERE p.id = $1 AND score > 0").unwrap(),
+        Cardinality::Optional
+    );
+    assert_eq!(
+        cardinality(r#":infer SELECT * FROM enrollment WHERE course = "math" AND pupil_id = $1"#)
+            .unwrap(),
+        Cardinality::Optional
+    );
+    assert_eq!(
+        cardinality(":infer SELECT * FROM enrollment WHERE pupil_id = $1").unwrap(),
+        Cardinality::Many
+    );
+    assert_eq!(
+        cardinality(":infer SELECT name FROM pupil WHERE id = $1 OR score = 1").unwrap(),
+        Cardinality::Many
+    );
+    assert_eq!(
+        cardinality(
+            ":infer SELECT p.name FROM pupil p JOIN teacher t ON t.id = p.teacher_id WHERE p.id = $1"
+        )
+        .unwrap(),
+        Cardinality::Many
+    );
+}
+
+#[test]
+fn cardinality_errors() {
+    assert_eq!(
+        cardinality(":one :opt SELECT id FROM pupil")
+            .unwrap_err()
+            .to_string(),
+        "only one of `:infer`, `:one` and `:opt` can be given"
+    );
+    assert_eq!(
+        cardinality(":one DELETE FROM pupil WHERE id = $1")
+            .unwrap_err()
+            .to_string(),
+        "`:one` requires a statement returning rows"
+    );
+}
+
+fn expand(sql: &str) -> String {
+    Sql {
+        schema: schema(),
+        statement: statement(sql),
+    }
+    .expand()
+    .to_string()
+}
+
+#[test]
+fn query_iter_streams_rows() {
+    let sql = "SELECT id, email FROM pupil WHERE score > $1 AND name = $2";
+    let text = statement(sql).to_string();
+    let expanded = expand(sql);
+
+    // the parameters are bound in order and each row is decoded while iterating
+    let streamed = quote! {
+        let params: [&dyn ::postgres::types::ToSql; 2usize] = [&p1, &p2];
+        let rows = client.query_raw(#text, params)?;
+        Ok(::postgres::fallible_iterator::FallibleIterator::map(rows, |row| Self::from_row(&row)))
+    };
+    assert!(expanded.contains(&streamed.to_string()));
+
+    let signature = quote! {
+        pub fn query_iter<'a>(client: &'a mut ::postgres::Client, p1: i32, p2: &str)
+    };
+    assert!(expanded.contains(&signature.to_string()));
+
+    let unbound = quote! {
+        let params: [&dyn ::postgres::types::ToSql; 0usize] = [];
+    };
+    assert!(expand("SELECT id FROM teacher").contains(&unbound.to_string()));
+
+    // statements without rows only execute
+    assert!(!expand("DELETE FROM pupil WHERE id = $1").contains("query_iter"));
+}
@@ -0,0 +1,8 @@
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" Case
+    SELECT p.name, t.name FROM pupil p JOIN teacher t ON t.id = p.teacher_id
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" Case
+    INSERT INTO enrollment (course) VALUES ($1)
+}
+
+fn main() {}
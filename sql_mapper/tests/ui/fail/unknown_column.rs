@@ -0,0 +1,8 @@
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" Case
+    SELECT nme FROM pupil
+}
+
+fn main() {}
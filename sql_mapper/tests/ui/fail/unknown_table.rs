@@ -0,0 +1,8 @@
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" Case
+    SELECT * FROM pupl
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" Case
+    SELECT score + 1 FROM pupil
+}
+
+fn main() {}
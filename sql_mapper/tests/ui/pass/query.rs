@@ -0,0 +1,76 @@
+use postgres::{fallible_iterator::FallibleIterator, Client, Error};
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" BestPupils
+    SELECT id, name, email FROM pupil WHERE score > $1 ORDER BY score DESC LIMIT $2
+}
+
+sql! {
+    -- "../schema.rs" PupilEmails
+    SELECT name, email AS "email!" FROM pupil WHERE email IS NOT NULL
+}
+
+sql! {
+    -- "../schema.rs" ScoresByTeacher
+    SELECT teacher_id, count(*) AS n, max(score) AS best FROM pupil GROUP BY teacher_id
+}
+
+sql! {
+    -- "../schema.rs" Contacts
+    SELECT name, email AS contact FROM pupil UNION SELECT name, NULL FROM teacher ORDER BY name
+}
+
+sql! {
+    -- "../schema.rs" PupilsByIds
+    SELECT id, p.name FROM unnest($1::int[]) AS id JOIN pupil p USING (id)
+}
+
+sql! {
+    -- "../schema.rs" PupilById :infer
+    SELECT name FROM pupil WHERE id = $1
+}
+
+sql! {
+    -- "../schema.rs" PupilCount :one
+    SELECT count(*) AS n FROM pupil
+}
+
+fn query(client: &mut Client) -> Result<(), Error> {
+    let pupils: Vec<BestPupils> = BestPupils::query(client, 5, 10)?;
+    let _: Option<&str> = pupils.first().and_then(|p| p.email.as_deref());
+
+    let emails: Vec<PupilEmails> = PupilEmails::query(client)?;
+    let _: Option<&str> = emails.first().map(|p| p.email.as_str());
+
+    let scores: Vec<ScoresByTeacher> = ScoresByTeacher::query(client)?;
+    let _: Option<(Option<i32>, i64, Option<i32>)> =
+        scores.first().map(|s| (s.teacher_id, s.n, s.best));
+
+    let contacts: Vec<Contacts> = Contacts::query(client)?;
+    let _: Option<(&str, Option<&str>)> = contacts
+        .first()
+        .map(|c| (c.name.as_str(), c.contact.as_deref()));
+
+    let pupils: Vec<PupilsByIds> = PupilsByIds::query(client, &[1, 2, 3])?;
+    let _: Option<(i32, &str)> = pupils.first().map(|p| (p.id, p.name.as_str()));
+
+    let pupil: Option<PupilById> = PupilById::query(client, 1)?;
+    let _: Option<&str> = pupil.as_ref().map(|p| p.name.as_str());
+    let _: PupilById = PupilById::query_one(client, 1)?;
+
+    let count: PupilCount = PupilCount::query(client)?;
+    let _: i64 = count.n;
+    let _: Option<PupilCount> = PupilCount::query_opt(client)?;
+
+    let mut pupils = BestPupils::query_iter(client, 5, 10)?;
+    while let Some(pupil) = pupils.next()? {
+        let _: (i32, &str) = (pupil.id, pupil.name.as_str());
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let _ = query;
+}
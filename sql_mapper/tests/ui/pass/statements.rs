@@ -0,0 +1,54 @@
+use postgres::{Client, Error};
+use sql_mapper::sql;
+
+sql! {
+    -- "../schema.rs" InsertPupil
+    INSERT INTO pupil (name, email, score) VALUES ($1, $2, $3)
+}
+
+sql! {
+    -- "../schema.rs" InsertPupilReturning
+    INSERT INTO pupil (name, score) VALUES ($1, $2) RETURNING id
+}
+
+sql! {
+    -- "../schema.rs" UpsertPupil
+    INSERT INTO pupil (id, name, score) VALUES ($1, $2, $3)
+    ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name
+}
+
+sql! {
+    -- "../schema.rs" RenamePupil :deny_key_update
+    UPDATE pupil SET name = $1 WHERE id = $2
+}
+
+sql! {
+    -- "../schema.rs" DeletePupil :require_where
+    DELETE FROM pupil WHERE id = $1 RETURNING name
+}
+
+sql! {
+    -- "../schema.rs" Teachers
+    SELECT * FROM teacher WHERE active = $1
+}
+
+fn statements(client: &mut Client) -> Result<(), Error> {
+    let _: u64 = InsertPupil::execute(client, "Robert Redrust", None, 1)?;
+    let inserted: Vec<InsertPupilReturning> = InsertPupilReturning::query(client, "Robert", 1)?;
+    let _: Option<i32> = inserted.first().map(|p| p.id);
+    let _: u64 = UpsertPupil::execute(client, 1, "Robert", 1)?;
+    let _: u64 = RenamePupil::execute(client, "Robert", 1)?;
+    let deleted: Vec<DeletePupil> = DeletePupil::query(client, 1)?;
+    let _: Option<&str> = deleted.first().map(|p| p.name.as_str());
+
+    let teachers: Vec<Teachers> = Teachers::query(client, true)?;
+    let _: Option<(i32, &str, bool)> = teachers
+        .first()
+        .map(|t| (t.teacher_id, t.teacher_name.as_str(), t.teacher_active));
+
+    Ok(())
+}
+
+fn main() {
+    let _ = statements;
+}
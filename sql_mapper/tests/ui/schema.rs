@@ -0,0 +1,25 @@
+table! {
+    pupil (id) {
+        id -> Int4,
+        name -> Text,
+        email -> Nullable<Text>,
+        teacher_id -> Nullable<Int4>,
+        score -> Int4,
+        created -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    teacher (id) {
+        id -> Int4,
+        name -> Text,
+        active -> Bool,
+    }
+}
+
+table! {
+    enrollment (pupil_id, course) {
+        pupil_id -> Int4,
+        course -> Text,
+    }
+}